@@ -52,21 +52,257 @@
 
 extern crate alloc;
 extern crate riscv;
-extern crate linked_list_allocator;
 extern crate spin;
 
 use core::alloc::{GlobalAlloc, Layout};
-use core::ptr::NonNull;
+use core::mem::size_of;
+use core::ptr;
 
 // use riscv::interrupt::Mutex;
 // mod mutex;
 // use mutex::Mutex;
 use spin::Mutex;
 
-use linked_list_allocator::Heap;
+/// number of segregated free-list buckets; bucket `i` holds free blocks whose size
+/// (including header) is at least `quantum << i`. The last bucket catches everything
+/// at or above `quantum << (NUM_CLASSES - 1)`.
+const NUM_CLASSES: usize = 12;
+
+const DEFAULT_QUANTUM: usize = 16;
+const DEFAULT_MINARENA: usize = 4096;
+
+/// header stored immediately before every block, free or allocated.
+///
+/// Free blocks additionally overlay a `next` pointer in the body immediately
+/// following the header, forming a singly-linked intrusive free list per bucket.
+#[repr(C)]
+struct BlockHeader {
+    /// total size of this block in bytes, header included
+    size: usize,
+    /// non-zero while the block sits in a free list
+    free: usize,
+}
+
+const HEADER_SIZE: usize = size_of::<BlockHeader>();
+/// smallest block we will ever hand out: header + room for the free-list `next` pointer
+const MIN_BLOCK: usize = HEADER_SIZE + size_of::<usize>();
+
+/// live statistics surfaced by [`RiscvHeap::stats`], useful for a UART log or debug
+/// screen to watch memory pressure on a long-running device.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeapStats {
+    /// bytes presently handed out to callers
+    pub current: usize,
+    /// high-water mark of `current` since `init()`
+    pub peak: usize,
+    /// bytes sitting idle in the free-list buckets
+    pub free: usize,
+    /// number of distinct free blocks outstanding (a coarse fragmentation proxy)
+    pub fragments: usize,
+}
+
+struct PoolHeap {
+    start: usize,
+    end: usize,
+    /// next never-yet-carved byte in the arena; everything below this has a valid header
+    bump: usize,
+    /// allocation granularity; every block size is rounded up to a multiple of this
+    quantum: usize,
+    /// size of a fresh chunk carved from the arena when a bucket search comes up empty
+    minarena: usize,
+    /// bucket `i` is the address of the first free block's header, or 0 if empty
+    free_lists: [usize; NUM_CLASSES],
+    stats: HeapStats,
+}
+
+impl PoolHeap {
+    const fn empty() -> Self {
+        PoolHeap {
+            start: 0,
+            end: 0,
+            bump: 0,
+            quantum: DEFAULT_QUANTUM,
+            minarena: DEFAULT_MINARENA,
+            free_lists: [0; NUM_CLASSES],
+            stats: HeapStats { current: 0, peak: 0, free: 0, fragments: 0 },
+        }
+    }
+
+    fn init(&mut self, start_addr: usize, size: usize, quantum: usize, minarena: usize) {
+        assert!(quantum.is_power_of_two(), "quantum must be a power of two");
+        self.start = start_addr;
+        self.end = start_addr + size;
+        self.bump = start_addr;
+        self.quantum = quantum;
+        self.minarena = minarena;
+        self.free_lists = [0; NUM_CLASSES];
+        self.stats = HeapStats { current: 0, peak: 0, free: 0, fragments: 0 };
+    }
+
+    fn round_up(&self, n: usize) -> usize {
+        (n + self.quantum - 1) & !(self.quantum - 1)
+    }
+
+    /// bucket index for a block of (at least) `size` bytes
+    fn class_for(&self, size: usize) -> usize {
+        let mut class = 0;
+        let mut threshold = self.quantum;
+        while class < NUM_CLASSES - 1 && threshold <= size {
+            threshold <<= 1;
+            class += 1;
+        }
+        // the loop overshoots by one class when size >= threshold, back off
+        if class > 0 && (self.quantum << class) > size {
+            class - 1
+        } else {
+            class
+        }
+    }
+
+    unsafe fn header_at(addr: usize) -> *mut BlockHeader {
+        addr as *mut BlockHeader
+    }
+
+    unsafe fn unlink_free(&mut self, class: usize, target: usize) {
+        let mut cursor = self.free_lists[class];
+        if cursor == target {
+            let next = ptr::read((cursor + HEADER_SIZE) as *const usize);
+            self.free_lists[class] = next;
+            return;
+        }
+        while cursor != 0 {
+            let next = ptr::read((cursor + HEADER_SIZE) as *const usize);
+            if next == target {
+                let target_next = ptr::read((target + HEADER_SIZE) as *const usize);
+                ptr::write((cursor + HEADER_SIZE) as *mut usize, target_next);
+                return;
+            }
+            cursor = next;
+        }
+    }
+
+    unsafe fn push_free(&mut self, addr: usize, size: usize) {
+        let class = self.class_for(size);
+        (*Self::header_at(addr)).size = size;
+        (*Self::header_at(addr)).free = 1;
+        ptr::write((addr + HEADER_SIZE) as *mut usize, self.free_lists[class]);
+        self.free_lists[class] = addr;
+        self.stats.free += size;
+        self.stats.fragments += 1;
+    }
+
+    /// carve a fresh chunk from the arena (at least `minarena`, or bigger if `need`
+    /// doesn't fit one) and drop it into the matching free bucket as one big block
+    fn carve(&mut self, need: usize) -> bool {
+        let chunk = if need > self.minarena { self.round_up(need) } else { self.minarena };
+        if self.bump + chunk > self.end {
+            return false;
+        }
+        let addr = self.bump;
+        self.bump += chunk;
+        unsafe { self.push_free(addr, chunk) };
+        true
+    }
+
+    /// find a free block of at least `need` bytes, splitting off and reinserting
+    /// the remainder if it's big enough to be useful on its own
+    fn take(&mut self, need: usize) -> Option<usize> {
+        let start_class = self.class_for(need);
+        for class in start_class..NUM_CLASSES {
+            let mut cursor = self.free_lists[class];
+            while cursor != 0 {
+                let size = unsafe { (*Self::header_at(cursor)).size };
+                if size >= need {
+                    unsafe { self.unlink_free(class, cursor) };
+                    self.stats.free -= size;
+                    self.stats.fragments -= 1;
+
+                    let remainder = size - need;
+                    if remainder >= MIN_BLOCK {
+                        unsafe {
+                            (*Self::header_at(cursor)).size = need;
+                            self.push_free(cursor + need, remainder);
+                        }
+                    } else {
+                        unsafe { (*Self::header_at(cursor)).size = size };
+                    }
+                    unsafe { (*Self::header_at(cursor)).free = 0 };
+                    return Some(cursor);
+                }
+                cursor = unsafe { ptr::read((cursor + HEADER_SIZE) as *const usize) };
+            }
+        }
+        None
+    }
+
+    /// every returned pointer must land on a `layout.align()` boundary, but a block's
+    /// header sits at a fixed, only-`quantum`-aligned address -- so alignments wider
+    /// than a pointer word can't just be the header address plus `HEADER_SIZE`. Instead
+    /// the block is over-sized by up to `align - 1` bytes of slack plus one pointer-sized
+    /// word, the returned pointer is bumped up to the next `align` boundary inside that
+    /// slack, and the true header address is stashed in the word immediately before the
+    /// returned pointer so `dealloc` can find it again without needing the layout back.
+    fn alloc(&mut self, layout: Layout) -> *mut u8 {
+        let align = layout.align().max(size_of::<usize>());
+        let need = self.round_up(HEADER_SIZE + (align - 1) + size_of::<usize>() + layout.size().max(1));
+
+        let addr = match self.take(need) {
+            Some(addr) => addr,
+            None => {
+                if !self.carve(need) {
+                    return ptr::null_mut();
+                }
+                match self.take(need) {
+                    Some(addr) => addr,
+                    None => return ptr::null_mut(),
+                }
+            }
+        };
+
+        let block_size = unsafe { (*Self::header_at(addr)).size };
+        self.stats.current += block_size;
+        if self.stats.current > self.stats.peak {
+            self.stats.peak = self.stats.current;
+        }
+
+        let data_floor = addr + HEADER_SIZE + size_of::<usize>();
+        let aligned = (data_floor + align - 1) & !(align - 1);
+        unsafe { ptr::write((aligned - size_of::<usize>()) as *mut usize, addr) };
+
+        aligned as *mut u8
+    }
+
+    unsafe fn dealloc(&mut self, ptr: *mut u8) {
+        let addr = ptr::read((ptr as usize - size_of::<usize>()) as *const usize);
+        let mut size = (*Self::header_at(addr)).size;
+        self.stats.current -= size;
+
+        // merge forward with any physically-adjacent free block(s); this crate only
+        // coalesces downstream neighbors, which is enough to keep the common
+        // alloc/free/alloc churn from fragmenting the arena into unusable slivers
+        let mut merged_addr = addr;
+        loop {
+            let next_addr = merged_addr + size;
+            if next_addr >= self.bump {
+                break;
+            }
+            let next_header = &*Self::header_at(next_addr);
+            if next_header.free == 0 {
+                break;
+            }
+            let next_size = next_header.size;
+            self.unlink_free(self.class_for(next_size), next_addr);
+            self.stats.free -= next_size;
+            self.stats.fragments -= 1;
+            size += next_size;
+        }
+
+        self.push_free(merged_addr, size);
+    }
+}
 
 pub struct RiscvHeap {
-    heap: Mutex<Heap>,
+    heap: Mutex<PoolHeap>,
 }
 
 impl RiscvHeap {
@@ -76,11 +312,13 @@ impl RiscvHeap {
     /// [`init`](struct.RiscvHeap.html#method.init) method before using the allocator.
     pub const fn empty() -> RiscvHeap {
         RiscvHeap {
-            heap: Mutex::new(Heap::empty()),
+            heap: Mutex::new(PoolHeap::empty()),
         }
     }
 
-    /// Initializes the heap
+    /// Initializes the heap with the default quantum/arena-chunk size. See
+    /// [`init_with_params`](#method.init_with_params) to tune those for a
+    /// particular workload.
     ///
     /// This function must be called BEFORE you run any code that makes use of the
     /// allocator.
@@ -89,14 +327,6 @@ impl RiscvHeap {
     ///
     /// `size` is the size of the heap in bytes.
     ///
-    /// Note that:
-    ///
-    /// - The heap grows "upwards", towards larger addresses. Thus `end_addr` must
-    ///   be larger than `start_addr`
-    ///
-    /// - The size of the heap is `(end_addr as usize) - (start_addr as usize)`. The
-    ///   allocator won't use the byte at `end_addr`.
-    ///
     /// # Unsafety
     ///
     /// Obey these or Bad Stuff will happen.
@@ -104,35 +334,60 @@ impl RiscvHeap {
     /// - This function must be called exactly ONCE.
     /// - `size > 0`
     pub unsafe fn init(&self, start_addr: usize, size: usize) {
-        //self.heap.init(start_addr, size);
-        //self.heap.lock(|heap| heap.init(start_addr, size));
-        self.heap.lock()
-        .init(start_addr, size);
+        self.init_with_params(start_addr, size, DEFAULT_QUANTUM, DEFAULT_MINARENA);
+    }
+
+    /// Same as [`init`](#method.init), but lets the caller tune the allocation
+    /// `quantum` (every block size is rounded up to a multiple of this) and
+    /// `minarena` (the size of each chunk carved from the backing region when a
+    /// free-list bucket search comes up empty) for the betrusted workload.
+    ///
+    /// # Unsafety
+    ///
+    /// Same requirements as [`init`](#method.init).
+    pub unsafe fn init_with_params(&self, start_addr: usize, size: usize, quantum: usize, minarena: usize) {
+        self.heap.lock().init(start_addr, size, quantum, minarena);
+    }
+
+    /// snapshot of current/peak allocation, free bytes, and outstanding free-block
+    /// count, for a UART log or debug screen to report memory pressure
+    pub fn stats(&self) -> HeapStats {
+        self.heap.lock().stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alloc_respects_requested_alignment() {
+        static mut ARENA: [u8; 4096] = [0; 4096];
+        let mut heap = PoolHeap::empty();
+        unsafe { heap.init(ARENA.as_mut_ptr() as usize, ARENA.len(), DEFAULT_QUANTUM, DEFAULT_MINARENA) };
+
+        for &align in &[8usize, 16, 32, 64, 128] {
+            let layout = Layout::from_size_align(37, align).unwrap();
+            let ptr = heap.alloc(layout);
+            assert!(!ptr.is_null(), "alloc failed for align {}", align);
+            assert_eq!(
+                ptr as usize % align,
+                0,
+                "pointer {:p} not aligned to {}",
+                ptr,
+                align
+            );
+            unsafe { heap.dealloc(ptr) };
+        }
     }
 }
 
 unsafe impl GlobalAlloc for RiscvHeap {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-//        self.heap.allocate_first_fit(layout).ok()
-//       .map_or(0 as *mut u8, |allocation| allocation.as_ptr()) }
-
-        //self.heap
-        //    .lock(|heap| heap.allocate_first_fit(layout))
-        //    .ok()
-        //    .map_or(0 as *mut u8, |allocation| allocation.as_ptr())
-
-        self.heap
-        .lock()
-        .allocate_first_fit(layout)
-        .ok()
-        .map_or(0 as *mut u8, |allocation| allocation.as_ptr())
-    }
-
-    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-//        (&mut *self).heap.deallocate(NonNull::new_unchecked(ptr), layout);
-//            .lock(|heap| heap.deallocate(NonNull::new_unchecked(ptr), layout));
-        self.heap
-        .lock()
-        .deallocate(NonNull::new_unchecked(ptr), layout)
+        self.heap.lock().alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        self.heap.lock().dealloc(ptr)
     }
 }
@@ -0,0 +1,218 @@
+//! Key/value settings (power state, backlight level, the efuse patch data,
+//! ...) that need to survive a reset, kept in a reserved flash region as an
+//! append-only log: each `set` or `rm` appends a record rather than erasing
+//! and rewriting the whole region, so normal use costs one write per change.
+//! The log is only rewound -- erased and replayed back in with dead entries
+//! dropped -- when it's full. On boot the whole log is replayed once to build
+//! the in-RAM view every `get`/`set`/`rm` actually works against.
+//!
+//! There's no flash controller in this tree yet, so [`ConfigFlash`] stands in
+//! for one, the same way `FlashIo` does for `fw_update`'s image slots -- an
+//! interface this module can be written against today and a real peripheral
+//! can satisfy later.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// a single flash region reserved for the config log
+pub trait ConfigFlash {
+    fn region_size(&self) -> u32;
+    fn erase(&mut self) -> Result<(), ConfigError>;
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), ConfigError>;
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), ConfigError>;
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// the underlying device reported a failure (program/erase error, timeout, ...)
+    Hardware,
+    /// `key` or `value` is too long to fit a record's length fields
+    TooLong,
+    /// the region filled up even after compaction (the live set alone doesn't fit)
+    Full,
+}
+
+/// marks the start of a valid record; erased flash reads back as `0xFF`, so
+/// this (or any byte other than `0xFF`) doubles as "there's a record here"
+const MAGIC: u8 = 0xA5;
+const FLAG_TOMBSTONE: u8 = 0x01;
+const HEADER_LEN: u32 = 5; // magic, flags, key_len, val_len (u16)
+
+/// appends `(key, value)` records to a `ConfigFlash` region and keeps an
+/// in-RAM view of the live (non-removed) entries so reads don't have to
+/// rescan the log
+pub struct Config<F: ConfigFlash> {
+    flash: F,
+    /// offset the next record will be appended at
+    tail: u32,
+    live: BTreeMap<String, Vec<u8>>,
+}
+
+impl<F: ConfigFlash> Config<F> {
+    /// replay the log already in `flash` to build the in-RAM view
+    pub fn mount(mut flash: F) -> Result<Self, ConfigError> {
+        let mut live = BTreeMap::new();
+        let mut offset = 0u32;
+
+        loop {
+            match Self::read_record(&mut flash, offset)? {
+                Some((key, value, tombstone, record_len)) => {
+                    if tombstone {
+                        live.remove(&key);
+                    } else {
+                        live.insert(key, value);
+                    }
+                    offset += record_len;
+                }
+                None => break,
+            }
+        }
+
+        Ok(Config { flash, tail: offset, live })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.live.get(key).map(|v| v.as_slice())
+    }
+
+    pub fn set(&mut self, key: &str, value: &[u8]) -> Result<(), ConfigError> {
+        self.append(key, value, false)?;
+        self.live.insert(String::from(key), Vec::from(value));
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &str) -> Result<(), ConfigError> {
+        if self.live.remove(key).is_some() {
+            self.append(key, &[], true)?;
+        }
+        Ok(())
+    }
+
+    /// erase the whole region and forget every entry
+    pub fn erase(&mut self) -> Result<(), ConfigError> {
+        self.flash.erase().map_err(|_| ConfigError::Hardware)?;
+        self.tail = 0;
+        self.live.clear();
+        Ok(())
+    }
+
+    /// append one record, compacting first if it wouldn't fit
+    fn append(&mut self, key: &str, value: &[u8], tombstone: bool) -> Result<(), ConfigError> {
+        if key.len() > core::u8::MAX as usize || value.len() > core::u16::MAX as usize {
+            return Err(ConfigError::TooLong);
+        }
+        let record_len = HEADER_LEN + key.len() as u32 + value.len() as u32;
+
+        if self.tail + record_len > self.flash.region_size() {
+            self.compact()?;
+            if self.tail + record_len > self.flash.region_size() {
+                return Err(ConfigError::Full);
+            }
+        }
+
+        let mut record = Vec::with_capacity(record_len as usize);
+        record.push(MAGIC);
+        record.push(if tombstone { FLAG_TOMBSTONE } else { 0 });
+        record.push(key.len() as u8);
+        record.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        record.extend_from_slice(key.as_bytes());
+        record.extend_from_slice(value);
+
+        self.flash.write(self.tail, &record).map_err(|_| ConfigError::Hardware)?;
+        self.tail += record_len;
+        Ok(())
+    }
+
+    /// erase the region and rewrite just the live entries, freeing up
+    /// whatever space was spent on tombstones and superseded values
+    fn compact(&mut self) -> Result<(), ConfigError> {
+        let entries: Vec<(String, Vec<u8>)> = self.live.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+
+        self.flash.erase().map_err(|_| ConfigError::Hardware)?;
+        self.tail = 0;
+
+        for (key, value) in entries {
+            self.append(&key, &value, false)?;
+        }
+        Ok(())
+    }
+
+    /// read one record at `offset`, returning `None` once the log runs into
+    /// erased (`0xFF`) flash
+    fn read_record(flash: &mut F, offset: u32) -> Result<Option<(String, Vec<u8>, bool, u32)>, ConfigError> {
+        if offset + HEADER_LEN > flash.region_size() {
+            return Ok(None);
+        }
+
+        let mut header = [0u8; HEADER_LEN as usize];
+        flash.read(offset, &mut header).map_err(|_| ConfigError::Hardware)?;
+        if header[0] != MAGIC {
+            return Ok(None); // erased space -- end of the log
+        }
+
+        let tombstone = (header[1] & FLAG_TOMBSTONE) != 0;
+        let key_len = header[2] as u32;
+        let val_len = u16::from_le_bytes([header[3], header[4]]) as u32;
+        let record_len = HEADER_LEN + key_len + val_len;
+        if offset + record_len > flash.region_size() {
+            return Ok(None); // truncated/corrupt tail record -- stop here
+        }
+
+        let mut key_bytes = Vec::new();
+        key_bytes.resize(key_len as usize, 0u8);
+        flash.read(offset + HEADER_LEN, &mut key_bytes).map_err(|_| ConfigError::Hardware)?;
+        let key = String::from_utf8(key_bytes).map_err(|_| ConfigError::Hardware)?;
+
+        let mut value = Vec::new();
+        value.resize(val_len as usize, 0u8);
+        flash.read(offset + HEADER_LEN + key_len, &mut value).map_err(|_| ConfigError::Hardware)?;
+
+        Ok(Some((key, value, tombstone, record_len)))
+    }
+}
+
+/// stand-in backing store until a real NOR flash peripheral shows up in
+/// `betrusted_pac` -- keeps the log in a fixed RAM buffer so `Config` can be
+/// mounted and exercised today. Nothing written here survives a reset; swap
+/// in a real `ConfigFlash` impl once the hardware does.
+pub struct RamConfigFlash {
+    data: [u8; Self::SIZE],
+}
+
+impl RamConfigFlash {
+    const SIZE: usize = 4096;
+
+    pub const fn new() -> Self {
+        RamConfigFlash { data: [0xFF; Self::SIZE] }
+    }
+}
+
+impl ConfigFlash for RamConfigFlash {
+    fn region_size(&self) -> u32 {
+        Self::SIZE as u32
+    }
+
+    fn erase(&mut self) -> Result<(), ConfigError> {
+        self.data = [0xFF; Self::SIZE];
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), ConfigError> {
+        let start = offset as usize;
+        if start + data.len() > Self::SIZE {
+            return Err(ConfigError::Hardware);
+        }
+        self.data[start..start + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), ConfigError> {
+        let start = offset as usize;
+        if start + buf.len() > Self::SIZE {
+            return Err(ConfigError::Hardware);
+        }
+        buf.copy_from_slice(&self.data[start..start + buf.len()]);
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+//! Configurable text cursor for the REPL input line, replacing a hardcoded
+//! underscore appended to the input string at a fixed 500 ms blink. The
+//! cursor is drawn as its own primitive over the rendered text instead of
+//! mutating the string, so it never corrupts the input buffer and its shape
+//! doesn't depend on there being a glyph that looks like an underscore.
+
+use embedded_graphics::drawable::Drawable;
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Line, Rectangle};
+use embedded_graphics::DrawTarget;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CursorStyle {
+    /// a filled rectangle the size of one character cell, terminal "block" style
+    Block,
+    /// a short line under the character cell's baseline
+    Underline,
+    /// a thin vertical line at the character cell's left edge, like most GUI text boxes
+    Bar,
+}
+
+/// blink rate and shape for the input caret. `blink_interval_ms` of `0`
+/// means steady -- always on, no blink -- rather than a magic sentinel like
+/// `u32::MAX` that a caller could plausibly also mean as "a very slow blink".
+#[derive(Clone, Copy)]
+pub struct CursorConfig {
+    pub style: CursorStyle,
+    pub blink_interval_ms: u32,
+}
+
+impl CursorConfig {
+    pub fn new(style: CursorStyle, blink_interval_ms: u32) -> Self {
+        CursorConfig { style, blink_interval_ms }
+    }
+
+    /// a 500 ms blinking bar: the same blink rate as the underscore caret
+    /// this replaces, drawn as a bar
+    pub fn default_config() -> Self {
+        CursorConfig { style: CursorStyle::Bar, blink_interval_ms: 500 }
+    }
+
+    fn visible(&self, now_ms: u32) -> bool {
+        self.blink_interval_ms == 0 || (now_ms / self.blink_interval_ms) % 2 == 0
+    }
+}
+
+/// draws the caret at the character cell starting at `origin`, sized
+/// `char_size` (the font's advance width and line height), if `config`'s
+/// blink phase is currently on at `now_ms`
+pub fn draw_cursor<D: DrawTarget<BinaryColor>>(display: &mut D, origin: Point, char_size: Size, config: &CursorConfig, now_ms: u32) {
+    if !config.visible(now_ms) {
+        return;
+    }
+
+    match config.style {
+        CursorStyle::Block => {
+            let corner = Point::new(origin.x + char_size.width as i32 - 1, origin.y + char_size.height as i32 - 1);
+            Rectangle::<BinaryColor>::new(origin, corner)
+                .stroke_color(Some(BinaryColor::On))
+                .fill_color(Some(BinaryColor::On))
+                .draw(display);
+        }
+        CursorStyle::Underline => {
+            let y = origin.y + char_size.height as i32 - 1;
+            Line::<BinaryColor>::new(Point::new(origin.x, y), Point::new(origin.x + char_size.width as i32 - 1, y))
+                .stroke_color(Some(BinaryColor::On))
+                .draw(display);
+        }
+        CursorStyle::Bar => {
+            Line::<BinaryColor>::new(origin, Point::new(origin.x, origin.y + char_size.height as i32 - 1))
+                .stroke_color(Some(BinaryColor::On))
+                .draw(display);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Dual-slot (A/B) firmware update over the COM mailbox.
+//!
+//! Two flash slots hold a full firmware image each; only one is "active" at a
+//! time, and an update always writes into the *other* slot, leaving the
+//! currently-running image untouched until the new one has been verified. The
+//! active-slot choice is a tiny record -- which slot, and a generation counter
+//! that each successful update bumps by one (`select_boot_slot` boots the slot
+//! the record names and does not compare generations) -- and since it's
+//! the one piece of state that bricks the device if it gets corrupted, it's
+//! protected the same way an efuse row is: encoded with [`efuse_ecc`], so a
+//! single flipped bit in the record is corrected rather than mistaken for a
+//! different slot choice.
+//!
+//! There's no flash controller in this tree yet, so [`FlashIo`] stands in for
+//! one the same way `JtagPhy` stands in for the JTAG pin driver in `jtag.rs`:
+//! an interface this module can be written against today, satisfied by a
+//! concrete peripheral later.
+
+use crate::hal_com::mailbox::{Mailbox, MailboxError};
+use efuse_ecc::efuse_ecc;
+use alloc::vec::Vec;
+
+/// number of flash slots available for firmware images (A and B)
+pub const NUM_SLOTS: u8 = 2;
+
+/// mailbox opcode this side uses to request the next chunk of the incoming
+/// image; the peer replies with an empty payload to signal end-of-image
+const OPCODE_FW_CHUNK: u8 = 0x10;
+
+/// operations a flash slot needs to support for the updater to use it. A real
+/// implementation backs this with whatever SPI-NOR (or similar) peripheral
+/// the board exposes; `erase_slot` must leave `slot` entirely erased before
+/// `write` is called against it.
+pub trait FlashIo {
+    /// size in bytes of one slot, including its header
+    fn slot_size(&self) -> u32;
+    fn erase_slot(&mut self, slot: u8) -> Result<(), FlashError>;
+    fn write(&mut self, slot: u8, offset: u32, data: &[u8]) -> Result<(), FlashError>;
+    fn read(&mut self, slot: u8, offset: u32, buf: &mut [u8]) -> Result<(), FlashError>;
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FlashError {
+    /// `offset`/length given to `write` or `read` ran past `slot_size`
+    OutOfRange,
+    /// the underlying device reported a failure (program/erase error, timeout, ...)
+    Hardware,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UpdateError {
+    /// the active-slot record failed its `efuse_ecc` check (two or more bad bits)
+    BadSelector,
+    Mailbox(MailboxError),
+    Flash(FlashError),
+    /// the image length reported by the sender doesn't fit in one slot
+    TooLarge,
+    /// the freshly-written slot didn't verify against its own header
+    VerifyFailed,
+}
+
+/// length + CRC32 written at the start of every slot
+struct ImageHeader {
+    length: u32,
+    crc32: u32,
+}
+
+const HEADER_BYTES: u32 = 8;
+
+impl ImageHeader {
+    fn to_bytes(&self) -> [u8; HEADER_BYTES as usize] {
+        let mut out = [0u8; HEADER_BYTES as usize];
+        out[0..4].copy_from_slice(&self.length.to_le_bytes());
+        out[4..8].copy_from_slice(&self.crc32.to_le_bytes());
+        out
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let mut length = [0u8; 4];
+        let mut crc32 = [0u8; 4];
+        length.copy_from_slice(&bytes[0..4]);
+        crc32.copy_from_slice(&bytes[4..8]);
+        ImageHeader { length: u32::from_le_bytes(length), crc32: u32::from_le_bytes(crc32) }
+    }
+}
+
+/// CRC32 (IEEE 802.3 polynomial), computed bit-at-a-time -- same tradeoff as
+/// `hal_com::mailbox`'s CRC16: simple and cheap enough for an update-sized
+/// buffer, no lookup table to maintain.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFF_FFFF;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            if (crc & 1) != 0 {
+                crc = (crc >> 1) ^ 0xEDB8_8320;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    !crc
+}
+
+/// which slot an active-slot record picks, and how to tell it apart from a
+/// record that's equally valid but older
+pub struct SlotRecord {
+    pub active_slot: u8,
+    pub generation: u8,
+}
+
+impl SlotRecord {
+    fn pack(&self) -> u32 {
+        ((self.generation as u32) << 1) | (self.active_slot as u32 & 1)
+    }
+
+    fn unpack(data: u32) -> Self {
+        SlotRecord { active_slot: (data & 1) as u8, generation: ((data >> 1) & 0xFF) as u8 }
+    }
+
+    /// encode this record the same way an efuse row is encoded, so a single
+    /// flipped bit in storage is corrected rather than bricking the device
+    pub fn encode(&self) -> u32 {
+        efuse_ecc::add_ecc(self.pack())
+    }
+
+    pub fn decode(word: u32) -> Result<Self, efuse_ecc::EccError> {
+        efuse_ecc::decode_ecc(word).map(Self::unpack)
+    }
+}
+
+/// read back a slot's header and confirm its image matches its own stored CRC
+fn verify_slot<F: FlashIo>(flash: &mut F, slot: u8) -> Result<(), UpdateError> {
+    let mut header_bytes = [0u8; HEADER_BYTES as usize];
+    flash.read(slot, 0, &mut header_bytes).map_err(UpdateError::Flash)?;
+    let header = ImageHeader::from_bytes(&header_bytes);
+
+    // blank/erased flash reads back as header.length == 0xFFFF_FFFF; a plain
+    // `HEADER_BYTES + header.length` wraps past that and can slip under
+    // slot_size(), so check for overflow instead of trusting the sum
+    if header.length == 0 || HEADER_BYTES.checked_add(header.length).map_or(true, |total| total > flash.slot_size()) {
+        return Err(UpdateError::VerifyFailed);
+    }
+
+    let mut body: Vec<u8> = Vec::new();
+    body.resize(header.length as usize, 0u8);
+    flash.read(slot, HEADER_BYTES, &mut body).map_err(UpdateError::Flash)?;
+
+    if crc32(&body) == header.crc32 {
+        Ok(())
+    } else {
+        Err(UpdateError::VerifyFailed)
+    }
+}
+
+/// pick the slot to boot: the record's active slot if it verifies, falling
+/// back to the other slot if it doesn't (e.g. an update was interrupted
+/// between erasing and verifying)
+pub fn select_boot_slot<F: FlashIo>(flash: &mut F, record_word: u32) -> Result<u8, UpdateError> {
+    let record = SlotRecord::decode(record_word).map_err(|_| UpdateError::BadSelector)?;
+
+    if verify_slot(flash, record.active_slot).is_ok() {
+        return Ok(record.active_slot);
+    }
+    let other = (record.active_slot + 1) % NUM_SLOTS;
+    if verify_slot(flash, other).is_ok() {
+        Ok(other)
+    } else {
+        Err(UpdateError::VerifyFailed)
+    }
+}
+
+/// receive a new image over `mailbox` into the slot that isn't `current`'s
+/// active one, verify it, and return the new (still-unwritten-to-storage)
+/// active-slot record on success. `progress` is called with 0..=100 as the
+/// image streams in, so e.g. `hal_lcd::draw_progress` can render a bar.
+pub fn receive_update<F: FlashIo>(
+    p: &betrusted_pac::Peripherals,
+    mailbox: &mut Mailbox,
+    flash: &mut F,
+    current_record: u32,
+    mut progress: impl FnMut(u8),
+) -> Result<u32, UpdateError> {
+    let current = SlotRecord::decode(current_record).map_err(|_| UpdateError::BadSelector)?;
+    let target = (current.active_slot + 1) % NUM_SLOTS;
+
+    flash.erase_slot(target).map_err(UpdateError::Flash)?;
+
+    let mut image: Vec<u8> = Vec::new();
+    let max_image_len = (flash.slot_size() - HEADER_BYTES) as usize;
+    loop {
+        let (_opcode, words) = mailbox.exchange(p, OPCODE_FW_CHUNK, &[]).map_err(UpdateError::Mailbox)?;
+        if words.is_empty() {
+            break; // sender signals end-of-image with an empty chunk
+        }
+        for word in words {
+            image.push((word & 0xFF) as u8);
+            image.push((word >> 8) as u8);
+        }
+        if image.len() > max_image_len {
+            return Err(UpdateError::TooLarge);
+        }
+        progress(((image.len() * 100) / max_image_len).min(100) as u8);
+    }
+
+    flash.write(target, HEADER_BYTES, &image).map_err(UpdateError::Flash)?;
+    let header = ImageHeader { length: image.len() as u32, crc32: crc32(&image) };
+    flash.write(target, 0, &header.to_bytes()).map_err(UpdateError::Flash)?;
+
+    verify_slot(flash, target)?;
+    progress(100);
+
+    Ok(SlotRecord { active_slot: target, generation: current.generation.wrapping_add(1) }.encode())
+}
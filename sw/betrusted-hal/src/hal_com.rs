@@ -1,5 +1,56 @@
 #[allow(dead_code)]
 
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::irq;
+use spin::Mutex;
+
+pub mod mailbox;
+
+/// guards the whole COM peripheral for the duration of a transaction, so a
+/// multi-word exchange (see `mailbox`) can't be interleaved with another
+/// caller's half-finished frame -- without this, two callers racing `com_txrx`
+/// could each see the other's clear/go/done sequence mid-swap and desync the
+/// link.
+static COM_LOCK: Mutex<()> = Mutex::new(());
+
+/// PLIC source id for COM's completion event. Arbitrary until `betrusted_pac`
+/// grows a real PLIC with an assigned id for this source.
+const COM_IRQ: usize = 2;
+
+static COM_IRQ_READY: AtomicBool = AtomicBool::new(false);
+
+/// wire up COM's "done" event so `wait_done` parks the core in `wfi` between
+/// checks instead of spinning tight. Safe to call more than once; only the
+/// first call takes effect.
+pub fn com_irq_init(p: &betrusted_pac::Peripherals) {
+    if COM_IRQ_READY.swap(true, Ordering::AcqRel) {
+        return; // already initialized
+    }
+    irq::register(COM_IRQ, 10, || {});
+    irq::enable(COM_IRQ);
+    p.COM.ev_pending.write(|w| unsafe { w.bits(p.COM.ev_pending.read().bits()) });
+    p.COM.ev_enable.write(|w| unsafe { w.bits(1) });
+}
+
+/// park the core in `wfi` until `COM.status.done` reads as `want`, instead of
+/// spinning tight. There's no real PLIC wired up yet to claim COM's completion
+/// as a distinct source, so each time `wfi` returns this checks COM's own
+/// `ev_pending` CSR itself and hands off to [`irq::set_pending`] / [`irq::dispatch`]
+/// in place of a hardware claim read, same as a HAL's ISR would once a real
+/// PLIC exists.
+fn wait_done(p: &betrusted_pac::Peripherals, want: bool) {
+    while p.COM.status.read().done().bit_is_set() != want {
+        unsafe {
+            riscv::asm::wfi();
+        }
+        if COM_IRQ_READY.load(Ordering::Acquire) && p.COM.ev_pending.read().bits() != 0 {
+            p.COM.ev_pending.write(|w| unsafe { w.bits(p.COM.ev_pending.read().bits()) });
+            irq::set_pending(COM_IRQ);
+            irq::dispatch();
+        }
+    }
+}
+
 /// com_txrx is a polled-implementation of an atomit TX/RX swap operation
 /// The code is a little awkward for several reasons:
 ///   * CSR space splits values longer than 8 bits into separate registers;
@@ -21,10 +72,19 @@
 /// is long enough that the CPU may actually see the stale done value after hitting
 /// go if the CPU is running on the fast side...
 pub fn com_txrx(p: &betrusted_pac::Peripherals, tx: u16) -> u16 {
+    let _guard = COM_LOCK.lock();
+    com_txrx_locked(p, tx)
+}
+
+/// the actual swap, assuming `COM_LOCK` is already held. `mailbox` uses this
+/// directly so it can hold the lock across every word of a framed exchange
+/// rather than re-acquiring it (and risking another caller's word landing in
+/// the middle of the frame) between words.
+pub(crate) fn com_txrx_locked(p: &betrusted_pac::Peripherals, tx: u16) -> u16 {
     // clear the done bit
     p.COM.control.write(|w| w.clrdone().bit(true));
     // wait until the done register clears
-    while p.COM.status.read().done().bit_is_set() { }
+    wait_done(p, false);
 
     // load the TX register
     unsafe{ p.COM.tx0.write(|w| w.bits((tx & 0xFF) as u32)); }
@@ -34,7 +94,7 @@ pub fn com_txrx(p: &betrusted_pac::Peripherals, tx: u16) -> u16 {
     p.COM.control.write(|w| w.go().bit(true));
 
     // wait until the done register is set
-    while !p.COM.status.read().done().bit_is_set() { }
+    wait_done(p, true);
 
     // grab the RX value and return it
     let rx: u16 = (p.COM.rx0.read().bits() as u16) | ((p.COM.rx1.read().bits() as u16) << 8);
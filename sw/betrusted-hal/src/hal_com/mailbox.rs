@@ -0,0 +1,127 @@
+//! Framed, CRC-checked messages layered over `com_txrx`'s bare word swap.
+//!
+//! `com_txrx` only exchanges one 16-bit word at a time with no notion of where
+//! a message starts or ends, so a single mis-timed swap leaves both sides
+//! reading each other's words out of step forever. A frame is:
+//!
+//!   header word: `opcode:8 | len:8`
+//!   seq word:    `seq:8 | ack:8`
+//!   `len` payload words
+//!   crc word:    CRC16 over the header, seq, and payload words
+//!
+//! `len` is fixed by the caller for a given exchange (the link is full-duplex
+//! and synchronous, so both sides clock exactly as many words as the caller
+//! sends); the peer is expected to echo back a frame of the same length. `seq`
+//! advances after every successful exchange and `ack` carries back the last `seq` this side
+//! received, so a reply that doesn't ack the frame we just sent -- or fails
+//! its CRC, or claims a different length than we sent -- is treated as a
+//! dropped or duplicated word and the whole frame is retried rather than
+//! trusting a desynced link.
+
+use super::{com_txrx_locked, COM_LOCK};
+use alloc::vec::Vec;
+
+/// largest payload, in words, a single frame can carry
+pub const MAX_PAYLOAD: usize = 16;
+
+const MAX_RETRIES: u8 = 3;
+
+/// errors `Mailbox::exchange` can report about a request/response round trip
+#[derive(Debug, PartialEq, Eq)]
+pub enum MailboxError {
+    /// `payload` was longer than `MAX_PAYLOAD`
+    TooLong,
+    /// every retry either failed its CRC, came back the wrong length, or
+    /// didn't ack the frame we sent -- the link never resynced
+    Desync,
+}
+
+/// CRC16/CCITT-FALSE (poly 0x1021, init 0xFFFF) over a big-endian byte view
+/// of `words`
+fn crc16(words: &[u16]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &word in words {
+        for byte in [(word >> 8) as u8, (word & 0xFF) as u8].iter() {
+            crc ^= (*byte as u16) << 8;
+            for _ in 0..8 {
+                if (crc & 0x8000) != 0 {
+                    crc = (crc << 1) ^ 0x1021;
+                } else {
+                    crc <<= 1;
+                }
+            }
+        }
+    }
+    crc
+}
+
+/// tracks the sequence numbers for one end of a framed COM exchange. The EC
+/// link has a single mailbox; callers share a `Mailbox` the same way they
+/// share the `COM` peripheral itself.
+pub struct Mailbox {
+    next_seq: u8,
+    last_rx_seq: u8,
+}
+
+impl Mailbox {
+    pub const fn new() -> Self {
+        Mailbox { next_seq: 1, last_rx_seq: 0 }
+    }
+
+    /// send `opcode`/`payload` as one frame and return the peer's opcode and
+    /// payload, retrying the whole frame up to `MAX_RETRIES` times if the
+    /// reply doesn't check out
+    pub fn exchange(&mut self, p: &betrusted_pac::Peripherals, opcode: u8, payload: &[u16]) -> Result<(u8, Vec<u16>), MailboxError> {
+        if payload.len() > MAX_PAYLOAD {
+            return Err(MailboxError::TooLong);
+        }
+
+        for _attempt in 0..=MAX_RETRIES {
+            let seq = self.next_seq;
+
+            let mut tx_frame: Vec<u16> = Vec::with_capacity(2 + payload.len());
+            tx_frame.push(((opcode as u16) << 8) | (payload.len() as u16));
+            tx_frame.push(((seq as u16) << 8) | (self.last_rx_seq as u16));
+            tx_frame.extend_from_slice(payload);
+            let tx_crc = crc16(&tx_frame);
+
+            let mut rx_frame: Vec<u16> = Vec::with_capacity(tx_frame.len());
+            {
+                // hold COM_LOCK across every word so another caller can't land
+                // a word in the middle of this frame
+                let _guard = COM_LOCK.lock();
+                for &word in tx_frame.iter() {
+                    rx_frame.push(com_txrx_locked(p, word));
+                }
+                let rx_crc = com_txrx_locked(p, tx_crc);
+                rx_frame.push(rx_crc);
+            }
+
+            let rx_crc = rx_frame[rx_frame.len() - 1];
+            let rx_body = &rx_frame[..rx_frame.len() - 1];
+            if crc16(rx_body) != rx_crc {
+                continue; // corrupted frame -- retry
+            }
+
+            let rx_header = rx_body[0];
+            let rx_opcode = (rx_header >> 8) as u8;
+            let rx_len = (rx_header & 0xFF) as usize;
+            if rx_len != payload.len() {
+                continue; // peer replied with a length we didn't clock -- desynced, retry
+            }
+
+            let rx_seqack = rx_body[1];
+            let rx_seq = (rx_seqack >> 8) as u8;
+            let rx_ack = (rx_seqack & 0xFF) as u8;
+            if rx_ack != seq {
+                continue; // peer hasn't acked this frame -- retry
+            }
+
+            self.last_rx_seq = rx_seq;
+            self.next_seq = self.next_seq.wrapping_add(1);
+            return Ok((rx_opcode, rx_body[2..2 + rx_len].to_vec()));
+        }
+
+        Err(MailboxError::Desync)
+    }
+}
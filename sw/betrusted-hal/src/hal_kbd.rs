@@ -1,11 +1,34 @@
 #[allow(dead_code)]
 
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::irq;
 
 /// note: the code is structured to use at most 16 rows or 16 cols
 const KBD_ROWS: usize = 9;
 const KBD_COLS: usize = 10;
 
+/// PLIC source id for the keyboard's rowchange event. Arbitrary until
+/// `betrusted_pac` grows a real PLIC with an assigned id for this source.
+const KBD_IRQ: usize = 3;
+
+static KBD_IRQ_READY: AtomicBool = AtomicBool::new(false);
+
+/// wire up the keyboard's rowchange event so `KeyManager::wait_for_event` parks
+/// the core in `wfi` between checks instead of spinning. Safe to call more than
+/// once; only the first call takes effect.
+pub fn kbd_irq_init(p: &betrusted_pac::Peripherals) {
+    if KBD_IRQ_READY.swap(true, Ordering::AcqRel) {
+        return; // already initialized
+    }
+    irq::register(KBD_IRQ, 10, || {});
+    irq::enable(KBD_IRQ);
+    p.KEYBOARD.ev_pending.write(|w| unsafe { w.bits(p.KEYBOARD.ev_pending.read().bits()) });
+    p.KEYBOARD.ev_enable.write(|w| unsafe { w.bits(1) });
+}
+
 /// Keyboard driver HAL. Very basic at the moment.
 /// 
 /// FIXME: add software debouncing once interrupts are working. At the moment, the system will
@@ -70,6 +93,40 @@ pub struct ScanCode {
     pub alt: Option<char>,    
 }
 
+/// which transition a [`KeyEvent`] represents
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    /// the original debounced keydown
+    Pressed,
+    /// a synthetic typematic repeat while the key stays held
+    Held,
+    /// the debounced keyup
+    Released,
+}
+
+/// one resolved keyboard transition: which physical key, what kind of
+/// transition, how many typematic repeats have fired for it since it was
+/// last pressed (`0` for the original press, saturating so a very long hold
+/// doesn't wrap), and the character it resolves to under the layout and
+/// modifier state active when the event fired (`None` for a modifier key
+/// itself, or a position with no mapping).
+pub struct KeyEvent {
+    pub code: (usize, usize),
+    pub state: KeyState,
+    pub repeats: u8,
+    pub ch: Option<char>,
+}
+
+/// raw code the blue-shift key emits -- its `key`/`shift`/`hold`/`alt` fields
+/// are all this same control code, since it never produces a literal
+/// character of its own
+const BLUE_SHIFT_CODE: char = 0xf_u8 as char;
+/// raw code the orange (sym) shift key emits in its `shift`/`hold` fields.
+/// Carried on the `,` key's location -- once modifier tracking resolves a
+/// key's variant, this location is the orange-shift modifier rather than a
+/// literal comma.
+const ORANGE_SHIFT_CODE: char = 0xe_u8 as char;
+
 /// This is the main keyboard manager construct.
 pub struct KeyManager {
     /// the peripheral access crate pointer
@@ -78,34 +135,226 @@ pub struct KeyManager {
     debounce: [[u8; KBD_COLS]; KBD_ROWS],
     /// threshold for considering an up or down event to be debounced, in loop interations
     threshold: u8,
+    /// loop iterations blue-shift has been continuously held
+    blue_hold_count: u16,
+    /// loop iterations orange-shift has been continuously held
+    orange_hold_count: u16,
+    /// blue-shift was tapped (pressed and released under the hold threshold)
+    /// and the one-shot shift it grants hasn't been applied to a key yet
+    blue_tap_pending: bool,
+    /// loop iterations a shift key must stay held to count as "hold" instead of "tap"
+    hold_threshold: u16,
+    /// the logical character table currently in effect; swappable at
+    /// runtime via `set_layout` without touching modifier tracking above
+    layout: Box<dyn KeyMap>,
+    /// loop iterations each key has been continuously held, for typematic
+    /// repeat -- analogous to `debounce`, but counts from the moment a key
+    /// is confirmed down rather than from the moment it's first seen
+    held_ticks: [[u16; KBD_COLS]; KBD_ROWS],
+    /// loop iterations since each key's last emitted repeat (or its initial
+    /// press), reset every time a repeat fires
+    since_last_repeat: [[u16; KBD_COLS]; KBD_ROWS],
+    /// how many synthetic repeats have fired for each held key since it was
+    /// last pressed, carried on emitted [`KeyEvent`]s so a consumer can tell
+    /// a fresh press (`0`) from a repeat
+    repeat_count: [[u32; KBD_COLS]; KBD_ROWS],
+    /// loop iterations a key must stay held before typematic repeat starts
+    repeat_initial_delay: u16,
+    /// loop iterations between each repeat once it's started
+    repeat_interval: u16,
+    /// whether the most recent `getcodes` scan found an ambiguous
+    /// (ghost-rectangle) shape, whether or not any cell of it was suppressed
+    last_ghosting: bool,
+    /// per-row active-column mask from the previous raw (pre-filter) scan,
+    /// so `filter_ghosting` can tell a cell that just became ambiguous this
+    /// round from one that was already part of a stable chord last round
+    prev_raw_mask: [u16; KBD_ROWS],
+    /// trigger position -> expansion, consulted on every fresh keydown
+    /// before falling back to the layout's single resolved `char`
+    macros: BTreeMap<(usize, usize), Vec<char>>,
 }
 
 impl KeyManager {
     pub fn new() -> Self {
-        unsafe{ 
+        unsafe{
             KeyManager{
                 p: betrusted_pac::Peripherals::steal(),
                 debounce: [[0; KBD_COLS]; KBD_ROWS],
                 threshold: 2,
+                blue_hold_count: 0,
+                orange_hold_count: 0,
+                blue_tap_pending: false,
+                hold_threshold: 20,
+                layout: Box::new(DvorakKeyMap),
+                held_ticks: [[0; KBD_COLS]; KBD_ROWS],
+                since_last_repeat: [[0; KBD_COLS]; KBD_ROWS],
+                repeat_count: [[0; KBD_COLS]; KBD_ROWS],
+                repeat_initial_delay: 400, // roughly 500ms worth of keyboard_task iterations
+                repeat_interval: 50,
+                last_ghosting: false,
+                prev_raw_mask: [0; KBD_ROWS],
+                macros: BTreeMap::new(),
+            }
+        }
+    }
+
+    /// swaps the active logical character table, e.g. from a settings menu.
+    /// Modifier state (held/tap tracking) carries over unchanged since it
+    /// lives on `KeyManager`, not the layout.
+    pub fn set_layout(&mut self, layout: Box<dyn KeyMap>) {
+        self.layout = layout;
+    }
+
+    /// configures typematic repeat: `initial_delay` loop iterations a key
+    /// must stay held before repeat starts, then one synthetic repeat every
+    /// `interval` iterations after that
+    pub fn set_repeat(&mut self, initial_delay: u16, interval: u16) {
+        self.repeat_initial_delay = initial_delay;
+        self.repeat_interval = interval.max(1);
+    }
+
+    /// binds `trigger` to emit `expansion` in order on keydown instead of
+    /// the single `char` its layout would otherwise resolve to -- e.g. an
+    /// F-key bound to an email address or a shell command. Registering
+    /// against a position already bound replaces its expansion. Held/repeat
+    /// behavior for a macro trigger still follows the layout's own
+    /// resolution, since replaying a whole expansion on every typematic
+    /// tick isn't useful.
+    pub fn register_macro(&mut self, trigger: (usize, usize), expansion: Vec<char>) {
+        self.macros.insert(trigger, expansion);
+    }
+
+    //// returns the current set of codes from the keyboard matrix, with
+    //// newly-ambiguous ghost cells filtered out (see `filter_ghosting`)
+    pub fn getcodes(&mut self) -> Option<Vec<(usize, usize)>> {
+        let raw = match kbd_getcodes(&self.p) {
+            Some(raw) => raw,
+            None => {
+                self.last_ghosting = false;
+                self.prev_raw_mask = [0; KBD_ROWS];
+                return None;
+            }
+        };
+        let (filtered, ghosting) = self.filter_ghosting(raw);
+        self.last_ghosting = ghosting;
+        if filtered.is_empty() {
+            None
+        } else {
+            Some(filtered)
+        }
+    }
+
+    /// true if the most recent `getcodes` scan found an ambiguous
+    /// (ghost-rectangle) shape, whether or not any cell of it was suppressed
+    pub fn ghosting_detected(&self) -> bool {
+        self.last_ghosting
+    }
+
+    /// the diodeless scan matrix can't distinguish "these three keys are
+    /// pressed" from "these three keys plus one more at the rectangle's
+    /// fourth corner" -- for every pair of rows whose active-column masks
+    /// share two or more columns, every cell at their intersection is
+    /// ambiguous. Already-debounced-down cells keep reporting (a key held
+    /// before the ambiguity arose shouldn't vanish because a later key
+    /// completed a ghost rectangle); cells that were already raw-present in
+    /// the *previous* scan keep reporting too, since a chord held steady
+    /// across two scans is a stable (if inherently ambiguous) shape rather
+    /// than a phantom that just appeared -- without this, a chord pressed
+    /// all at once from a cold start (nothing yet debounced) would never
+    /// debounce at all, because every round would keep re-suppressing the
+    /// same still-brand-new cells. Only a cell that's newly-ambiguous *and*
+    /// wasn't present last scan either is suppressed. Returns the filtered
+    /// code list and whether any ambiguity was found at all.
+    fn filter_ghosting(&mut self, codes: Vec<(usize, usize)>) -> (Vec<(usize, usize)>, bool) {
+        let mut row_masks = [0u16; KBD_ROWS];
+        for &(r, c) in &codes {
+            row_masks[r] |= 1 << c;
+        }
+
+        let mut blocked_mask = [0u16; KBD_ROWS];
+        let mut ghosting = false;
+        for r1 in 0..KBD_ROWS {
+            for r2 in (r1 + 1)..KBD_ROWS {
+                let shared = row_masks[r1] & row_masks[r2];
+                if shared.count_ones() >= 2 {
+                    ghosting = true;
+                    blocked_mask[r1] |= shared;
+                    blocked_mask[r2] |= shared;
+                }
             }
         }
+
+        let prev_raw_mask = self.prev_raw_mask;
+        self.prev_raw_mask = row_masks;
+
+        if !ghosting {
+            return (codes, false);
+        }
+
+        let filtered = codes
+            .into_iter()
+            .filter(|&(r, c)| {
+                (blocked_mask[r] & (1 << c)) == 0
+                    || self.debounce[r][c] >= self.threshold
+                    || (prev_raw_mask[r] & (1 << c)) != 0
+            })
+            .collect();
+
+        (filtered, true)
+    }
+
+    /// block until the keyboard matrix reports a row change, parking the core in
+    /// `wfi` between checks instead of spinning. There's no real PLIC wired up
+    /// yet to claim the rowchange event as a distinct source, so each time `wfi`
+    /// returns this checks the keyboard's own `ev_pending` CSR itself and hands
+    /// off to [`irq::set_pending`] / [`irq::dispatch`] in place of a hardware
+    /// claim read, same as `hal_com::wait_done` does for COM's completion event.
+    /// Callers that need debouncing should keep polling `update()` every main
+    /// loop iteration instead -- this is for a caller that can afford to block.
+    pub fn wait_for_event(&self) {
+        while kbd_rowchange(&self.p) == 0 {
+            unsafe {
+                riscv::asm::wfi();
+            }
+            if KBD_IRQ_READY.load(Ordering::Acquire) && self.p.KEYBOARD.ev_pending.read().bits() != 0 {
+                self.p.KEYBOARD.ev_pending.write(|w| unsafe { w.bits(self.p.KEYBOARD.ev_pending.read().bits()) });
+                irq::set_pending(KBD_IRQ);
+                irq::dispatch();
+            }
+        }
+    }
+
+
+    /// true if any currently-debounced-down key satisfies `pred`
+    fn any_pressed<F: Fn((usize, usize)) -> bool>(&self, pred: F) -> bool {
+        for r in 0..KBD_ROWS {
+            for c in 0..KBD_COLS {
+                if self.debounce[r][c] >= self.threshold && pred((r, c)) {
+                    return true;
+                }
+            }
+        }
+        false
     }
 
-    //// returns the current set of codes from the keyboard matrix
-    pub fn getcodes(&self) -> Option<Vec<(usize, usize)>> {
-        kbd_getcodes(&self.p)
+    fn is_blue_shift(&self, code: (usize, usize)) -> bool {
+        self.layout.lookup(code).hold == Some(BLUE_SHIFT_CODE)
     }
-    
+
+    fn is_orange_shift(&self, code: (usize, usize)) -> bool {
+        self.layout.lookup(code).hold == Some(ORANGE_SHIFT_CODE)
+    }
+
     /// update() is designed to be called at regular intervals (not based on keyboard interrupt)
-    /// by feeding the results of getcodes() to update the debounce matrix. Because this does 
+    /// by feeding the results of getcodes() to update the debounce matrix. Because this does
     /// debounce it needs to be aware of static key config info, whereas the keyboard interrupt only
     /// tells you if something has changed in the keyboard state.
-    /// 
+    ///
     /// A potential optimization would be for update to keep a copy of the last codes returned
     /// by the getcodes() function, which would allow this to go back to an interrupt-driven update.
-    /// 
+    ///
     /// returns a tuple of (keydown, keyup) scan codes, each of which are an Option-wrapped vector
-    pub fn update(&mut self, codes: Option<Vec<(usize,usize)>>) -> (Option<Vec<(usize, usize)>>, Option<Vec<(usize,usize)>>) {
+    fn debounce_update(&mut self, codes: Option<Vec<(usize,usize)>>) -> (Option<Vec<(usize, usize)>>, Option<Vec<(usize,usize)>>) {
         let mut downs: [[bool; KBD_COLS]; KBD_ROWS] = [[false; KBD_COLS]; KBD_ROWS];
         let mut keydowns = Vec::new();
         let mut keyups = Vec::new();
@@ -114,10 +363,15 @@ impl KeyManager {
             Some(code) => {
                 for key in code {
                     let (row, col) = key;
+                    // record that the scan still sees this key down, independent of
+                    // whether its debounce counter has already saturated at
+                    // `threshold` -- otherwise a continuously-held key stops being
+                    // marked "down" the moment it debounces, and the decay loop
+                    // below starts ticking it back down every other scan
+                    downs[row][col] = true;
                     if self.debounce[row][col] < self.threshold {
                         self.debounce[row][col] += 1;
-                        downs[row][col] = true;  // record that we did a keydown event
-                        // now check if we've passed the debounce threshold, and report a keydown                        
+                        // now check if we've passed the debounce threshold, and report a keydown
                         if self.debounce[row][col] == self.threshold {
                             keydowns.push((row,col));
                         }
@@ -157,23 +411,229 @@ impl KeyManager {
 
         (retdowns, retups)
     }
+
+    /// scans the matrix, debounces it, and resolves newly-pressed keys to
+    /// finished `char`s, tracking blue-shift/orange-shift as described on
+    /// [`ScanCode`] instead of leaving that to every caller. Blue-shift
+    /// tapped (pressed and released before `hold_threshold` loop
+    /// iterations) applies a one-shot `shift` to the very next key; held
+    /// past the threshold it applies `hold` to keys pressed while it's
+    /// down. Holding orange-shift applies `alt` the same way, with no tap
+    /// behavior since there's no one-shot "sym" convention to match.
+    ///
+    /// Returns every resolved transition this round: `Pressed` for a fresh
+    /// debounced keydown, `Held` for each synthetic typematic repeat, and
+    /// `Released` for a debounced keyup -- one combined vector instead of
+    /// separate keydown/keyup code lists, so a caller doesn't have to diff
+    /// vectors itself to tell a press from a repeat. A keydown on a position
+    /// bound via [`register_macro`] emits its whole expansion as consecutive
+    /// `Pressed` events instead of the layout's single resolved `char`.
+    pub fn update(&mut self) -> Vec<KeyEvent> {
+        let codes = self.getcodes();
+        let (keydowns, keyups) = self.debounce_update(codes);
+
+        let blue_held = self.any_pressed(|code| self.is_blue_shift(code));
+        let orange_held = self.any_pressed(|code| self.is_orange_shift(code));
+
+        if blue_held {
+            self.blue_hold_count = self.blue_hold_count.saturating_add(1);
+        } else {
+            if self.blue_hold_count > 0 && self.blue_hold_count < self.hold_threshold {
+                self.blue_tap_pending = true;
+            }
+            self.blue_hold_count = 0;
+        }
+
+        if orange_held {
+            self.orange_hold_count = self.orange_hold_count.saturating_add(1);
+        } else {
+            self.orange_hold_count = 0;
+        }
+
+        let blue_hold_active = self.blue_hold_count >= self.hold_threshold;
+
+        let mut events = Vec::new();
+
+        // fresh presses: reset this key's repeat timers and emit a Pressed
+        // event, consuming a pending blue-shift tap if there is one
+        if let Some(downs) = &keydowns {
+            for &(r, c) in downs {
+                self.held_ticks[r][c] = 0;
+                self.since_last_repeat[r][c] = 0;
+                self.repeat_count[r][c] = 0;
+
+                if let Some(expansion) = self.macros.get(&(r, c)) {
+                    // a bound chord expands into its whole sequence at once,
+                    // each char carried on its own Pressed event so a caller
+                    // that just reads `ch` off every event needs no special
+                    // casing for macros vs. a plain keypress
+                    for &ch in expansion {
+                        events.push(KeyEvent { code: (r, c), state: KeyState::Pressed, repeats: 0, ch: Some(ch) });
+                    }
+                    continue;
+                }
+
+                let ch = if self.is_blue_shift((r, c)) || self.is_orange_shift((r, c)) {
+                    None // modifiers don't themselves emit characters
+                } else {
+                    let scancode = self.layout.lookup((r, c));
+                    let tap_active = self.blue_tap_pending;
+                    if tap_active {
+                        self.blue_tap_pending = false;
+                    }
+                    resolve_variant(&scancode, blue_hold_active, orange_held, tap_active)
+                };
+                events.push(KeyEvent { code: (r, c), state: KeyState::Pressed, repeats: 0, ch });
+            }
+        }
+
+        // releases: stop repeating
+        if let Some(ups) = &keyups {
+            for &(r, c) in ups {
+                self.held_ticks[r][c] = 0;
+                self.since_last_repeat[r][c] = 0;
+                self.repeat_count[r][c] = 0;
+
+                let ch = if self.is_blue_shift((r, c)) || self.is_orange_shift((r, c)) {
+                    None
+                } else {
+                    resolve_variant(&self.layout.lookup((r, c)), blue_hold_active, orange_held, false)
+                };
+                events.push(KeyEvent { code: (r, c), state: KeyState::Released, repeats: 0, ch });
+            }
+        }
+
+        // typematic repeat for keys still held from a previous round
+        for r in 0..KBD_ROWS {
+            for c in 0..KBD_COLS {
+                if self.debounce[r][c] < self.threshold {
+                    continue; // not currently down
+                }
+                let code = (r, c);
+                let is_fresh = keydowns.as_ref().map_or(false, |d| d.contains(&code));
+                if is_fresh || self.is_blue_shift(code) || self.is_orange_shift(code) {
+                    continue;
+                }
+
+                self.held_ticks[r][c] = self.held_ticks[r][c].saturating_add(1);
+                if self.held_ticks[r][c] < self.repeat_initial_delay {
+                    continue;
+                }
+
+                self.since_last_repeat[r][c] = self.since_last_repeat[r][c].saturating_add(1);
+                if self.since_last_repeat[r][c] >= self.repeat_interval {
+                    self.since_last_repeat[r][c] = 0;
+                    self.repeat_count[r][c] = self.repeat_count[r][c].saturating_add(1);
+
+                    let scancode = self.layout.lookup(code);
+                    let ch = resolve_variant(&scancode, blue_hold_active, orange_held, false);
+                    let repeats = self.repeat_count[r][c].min(u8::MAX as u32) as u8;
+                    events.push(KeyEvent { code, state: KeyState::Held, repeats, ch });
+                }
+            }
+        }
+
+        events
+    }
+
+    /// true if `code` is currently debounced down
+    pub fn is_pressed(&self, code: (usize, usize)) -> bool {
+        self.debounce[code.0][code.1] >= self.threshold
+    }
+
+    /// blocks until any key produces a debounced `Pressed` event, for
+    /// interactive code (menus, confirmation prompts, games) that just
+    /// wants the next keypress rather than a per-iteration event vector.
+    /// Parks in `wfi` between scans via `wait_for_event`, same tradeoff as
+    /// that method: callers needing debounce on every iteration regardless
+    /// should poll `update()` instead.
+    pub fn wait_any_key(&mut self) -> (usize, usize) {
+        loop {
+            self.wait_for_event();
+            for event in self.update() {
+                if event.state == KeyState::Pressed {
+                    return event.code;
+                }
+            }
+        }
+    }
+}
+
+/// picks which of a [`ScanCode`]'s four variants applies given the current
+/// modifier state: held blue-shift wins over held orange-shift, which wins
+/// over a pending one-shot blue-shift tap, which wins over the plain key
+fn resolve_variant(scancode: &ScanCode, blue_hold_active: bool, orange_held: bool, blue_tap_active: bool) -> Option<char> {
+    if blue_hold_active {
+        scancode.hold
+    } else if orange_held {
+        scancode.alt
+    } else if blue_tap_active {
+        scancode.shift
+    } else {
+        scancode.key
+    }
+}
+
+/// separates the physical scan -> key-location mapping from the logical
+/// character table it resolves to, the way a configurable key-mapper keeps
+/// the scan matrix and the layout apart. `KeyManager` holds one behind a
+/// `Box<dyn KeyMap>` so a settings menu can call `set_layout` and swap
+/// layouts without rebuilding firmware.
+pub trait KeyMap {
+    fn lookup(&self, code: (usize, usize)) -> ScanCode;
+}
+
+/// key positions that mean the same thing no matter which logical layout is
+/// active: digits, backspace, enter, space, the function-key column, the
+/// arrow keys, the "OK" key, and the two shift modifiers. Letter-row
+/// positions return `None` so a [`KeyMap`] impl can fill them in with its
+/// own alphabet.
+fn fixed_function_lookup(code: (usize, usize)) -> Option<ScanCode> {
+    match code {
+        (0, 0) => Some(ScanCode{key: Some('1'), shift: Some('1'), hold: None, alt: None}),
+        (0, 1) => Some(ScanCode{key: Some('2'), shift: Some('2'), hold: None, alt: None}),
+        (0, 2) => Some(ScanCode{key: Some('3'), shift: Some('3'), hold: None, alt: None}),
+        (0, 3) => Some(ScanCode{key: Some('4'), shift: Some('4'), hold: None, alt: None}),
+        (0, 4) => Some(ScanCode{key: Some('5'), shift: Some('5'), hold: None, alt: None}),
+        (4, 5) => Some(ScanCode{key: Some('6'), shift: Some('6'), hold: None, alt: None}),
+        (4, 6) => Some(ScanCode{key: Some('7'), shift: Some('7'), hold: None, alt: None}),
+        (4, 7) => Some(ScanCode{key: Some('8'), shift: Some('8'), hold: None, alt: None}),
+        (4, 8) => Some(ScanCode{key: Some('9'), shift: Some('9'), hold: None, alt: None}),
+        (4, 9) => Some(ScanCode{key: Some('0'), shift: Some('0'), hold: None, alt: None}),
+
+        (1, 0) => Some(ScanCode{key: Some(0x8_u8.into()), shift: Some(0x8_u8.into()), hold: Some(0x8_u8.into()), alt: Some(0x8_u8.into())}), // backspace
+        (7, 9) => Some(ScanCode{key: Some(0xd_u8.into()), shift: Some(0xd_u8.into()), hold: Some(0xd_u8.into()), alt: Some(0xd_u8.into())}), // carriage return
+
+        (8, 5) => Some(ScanCode{key: Some(BLUE_SHIFT_CODE), shift: Some(BLUE_SHIFT_CODE), hold: Some(BLUE_SHIFT_CODE), alt: Some(BLUE_SHIFT_CODE)}), // shift in (blue shift)
+        (8, 9) => Some(ScanCode{key: Some(BLUE_SHIFT_CODE), shift: Some(BLUE_SHIFT_CODE), hold: Some(BLUE_SHIFT_CODE), alt: Some(BLUE_SHIFT_CODE)}), // shift in (blue shift)
+        (8, 6) => Some(ScanCode{key: Some(','), shift: Some(ORANGE_SHIFT_CODE), hold: Some(ORANGE_SHIFT_CODE), alt: None}),  // shift out (sym)
+        (8, 7) => Some(ScanCode{key: Some(' '), shift: Some(' '), hold: Some(' '), alt: None}),
+        (8, 8) => Some(ScanCode{key: Some('.'), shift: Some('😃'), hold: Some('😃'), alt: None}),
+
+        // these are all bugged: row values are swapped on PCB
+        (5, 0) => Some(ScanCode{key: Some(0x11_u8.into()), shift: Some(0x11_u8.into()), hold: Some(0x11_u8.into()), alt: Some(0x11_u8.into())}), // DC1 (F1)
+        (5, 1) => Some(ScanCode{key: Some(0x12_u8.into()), shift: Some(0x12_u8.into()), hold: Some(0x12_u8.into()), alt: Some(0x12_u8.into())}), // DC2 (F2)
+        (1, 8) => Some(ScanCode{key: Some(0x13_u8.into()), shift: Some(0x13_u8.into()), hold: Some(0x13_u8.into()), alt: Some(0x13_u8.into())}), // DC3 (F3)
+        (1, 9) => Some(ScanCode{key: Some(0x14_u8.into()), shift: Some(0x14_u8.into()), hold: Some(0x14_u8.into()), alt: Some(0x14_u8.into())}), // DC4 (F4)
+        (5, 3) => Some(ScanCode{key: Some('←'), shift: Some('←'), hold: Some('←'), alt: Some('←')}),
+        (1, 6) => Some(ScanCode{key: Some('→'), shift: Some('→'), hold: Some('→'), alt: Some('→')}),
+        (6, 4) => Some(ScanCode{key: Some('↑'), shift: Some('↑'), hold: Some('↑'), alt: Some('↑')}),
+        // this one is OK
+        (5, 2) => Some(ScanCode{key: Some('∴'), shift: Some('∴'), hold: Some('∴'), alt: Some('∴')}),
+
+        _ => None,
+    }
 }
 
-    /// Compute the dvorak key mapping of row/col to key tuples
-    pub fn map_dvorak(code: (usize,usize)) -> ScanCode {
+/// Dvorak Simplified Keyboard letter layout
+pub struct DvorakKeyMap;
+
+impl KeyMap for DvorakKeyMap {
+    fn lookup(&self, code: (usize, usize)) -> ScanCode {
+        if let Some(sc) = fixed_function_lookup(code) {
+            return sc;
+        }
         match code {
-            (0, 0) => ScanCode{key: Some('1'), shift: Some('1'), hold: None, alt: None},
-            (0, 1) => ScanCode{key: Some('2'), shift: Some('2'), hold: None, alt: None},
-            (0, 2) => ScanCode{key: Some('3'), shift: Some('3'), hold: None, alt: None},
-            (0, 3) => ScanCode{key: Some('4'), shift: Some('4'), hold: None, alt: None},
-            (0, 4) => ScanCode{key: Some('5'), shift: Some('5'), hold: None, alt: None},
-            (4, 5) => ScanCode{key: Some('6'), shift: Some('6'), hold: None, alt: None},
-            (4, 6) => ScanCode{key: Some('7'), shift: Some('7'), hold: None, alt: None},
-            (4, 7) => ScanCode{key: Some('8'), shift: Some('8'), hold: None, alt: None},
-            (4, 8) => ScanCode{key: Some('9'), shift: Some('9'), hold: None, alt: None},
-            (4, 9) => ScanCode{key: Some('0'), shift: Some('0'), hold: None, alt: None},
-
-            (1, 0) => ScanCode{key: Some(0x8_u8.into()), shift: Some(0x8_u8.into()), hold: Some(0x8_u8.into()), alt: Some(0x8_u8.into())}, // backspace
             (1, 1) => ScanCode{key: Some('\''), shift: Some('\''), hold: Some('@'), alt: None},
             (1, 2) => ScanCode{key: Some('p'), shift: Some('P'), hold: Some('#'), alt: None},
             (1, 3) => ScanCode{key: Some('y'), shift: Some('Y'), hold: Some('&'), alt: None},
@@ -204,25 +664,62 @@ impl KeyManager {
             (7, 6) => ScanCode{key: Some('w'), shift: Some('W'), hold: Some('^'), alt: None},
             (7, 7) => ScanCode{key: Some('v'), shift: Some('V'), hold: Some('='), alt: None},
             (7, 8) => ScanCode{key: Some('z'), shift: Some('Z'), hold: Some('%'), alt: None},
-            (7, 9) => ScanCode{key: Some(0xd_u8.into()), shift: Some(0xd_u8.into()), hold: Some(0xd_u8.into()), alt: Some(0xd_u8.into())}, // carriage return
-
-            (8, 5) => ScanCode{key: Some(0xf_u8.into()), shift: Some(0xf_u8.into()), hold: Some(0xf_u8.into()), alt: Some(0xf_u8.into())}, // shift in (blue shift)
-            (8, 6) => ScanCode{key: Some(','), shift: Some(0xe_u8.into()), hold: Some(0xe_u8.into()), alt: None},  // 0xe is shift out (sym)
-            (8, 7) => ScanCode{key: Some(' '), shift: Some(' '), hold: Some(' '), alt: None},
-            (8, 8) => ScanCode{key: Some('.'), shift: Some('😃'), hold: Some('😃'), alt: None},
-            (8, 9) => ScanCode{key: Some(0xf_u8.into()), shift: Some(0xf_u8.into()), hold: Some(0xf_u8.into()), alt: Some(0xf_u8.into())}, // shift in (blue shift)
-
-            // these are all bugged: row values are swapped on PCB
-            (5, 0) => ScanCode{key: Some(0x11_u8.into()), shift: Some(0x11_u8.into()), hold: Some(0x11_u8.into()), alt: Some(0x11_u8.into())}, // DC1 (F1)
-            (5, 1) => ScanCode{key: Some(0x12_u8.into()), shift: Some(0x12_u8.into()), hold: Some(0x12_u8.into()), alt: Some(0x12_u8.into())}, // DC2 (F2)
-            (1, 8) => ScanCode{key: Some(0x13_u8.into()), shift: Some(0x13_u8.into()), hold: Some(0x13_u8.into()), alt: Some(0x13_u8.into())}, // DC3 (F3)
-            (1, 9) => ScanCode{key: Some(0x14_u8.into()), shift: Some(0x14_u8.into()), hold: Some(0x14_u8.into()), alt: Some(0x14_u8.into())}, // DC4 (F4)
-            (5, 3) => ScanCode{key: Some('←'), shift: Some('←'), hold: Some('←'), alt: Some('←')},
-            (1, 6) => ScanCode{key: Some('→'), shift: Some('→'), hold: Some('→'), alt: Some('→')},
-            (6, 4) => ScanCode{key: Some('↑'), shift: Some('↑'), hold: Some('↑'), alt: Some('↑')},
-            // this one is OK
-            (5, 2) => ScanCode{key: Some('∴'), shift: Some('∴'), hold: Some('∴'), alt: Some('∴')},
-
-            _ => ScanCode {key: None, shift: None, hold: None, alt: None}
-        }
-    }
\ No newline at end of file
+
+            _ => ScanCode {key: None, shift: None, hold: None, alt: None},
+        }
+    }
+}
+
+/// QWERTY letter layout over the same physical slots as [`DvorakKeyMap`].
+/// Each position's `hold` symbol is carried over unchanged from the
+/// Dvorak table -- those are a physical slot's chorded extra, not part of
+/// "the alphabet" a layout swap is meant to change.
+pub struct QwertyKeyMap;
+
+impl KeyMap for QwertyKeyMap {
+    fn lookup(&self, code: (usize, usize)) -> ScanCode {
+        if let Some(sc) = fixed_function_lookup(code) {
+            return sc;
+        }
+        match code {
+            (1, 1) => ScanCode{key: Some('q'), shift: Some('Q'), hold: Some('@'), alt: None},
+            (1, 2) => ScanCode{key: Some('w'), shift: Some('W'), hold: Some('#'), alt: None},
+            (1, 3) => ScanCode{key: Some('e'), shift: Some('E'), hold: Some('&'), alt: None},
+            (1, 4) => ScanCode{key: Some('r'), shift: Some('R'), hold: Some('*'), alt: None},
+            (5, 5) => ScanCode{key: Some('t'), shift: Some('T'), hold: Some('-'), alt: None},
+            (5, 6) => ScanCode{key: Some('y'), shift: Some('Y'), hold: Some('+'), alt: None},
+            (5, 7) => ScanCode{key: Some('u'), shift: Some('U'), hold: Some('('), alt: None},
+            (5, 8) => ScanCode{key: Some('i'), shift: Some('I'), hold: Some(')'), alt: None},
+            (5, 9) => ScanCode{key: Some('p'), shift: Some('P'), hold: Some('!'), alt: None},
+
+            (2, 0) => ScanCode{key: Some('a'), shift: Some('A'), hold: Some('\\'), alt: None},
+            (2, 1) => ScanCode{key: Some('s'), shift: Some('S'), hold: Some('`'), alt: None},
+            (2, 2) => ScanCode{key: Some('d'), shift: Some('D'), hold: Some('~'), alt: None},
+            (2, 3) => ScanCode{key: Some('f'), shift: Some('F'), hold: Some('|'), alt: None},
+            (2, 4) => ScanCode{key: Some('g'), shift: Some('G'), hold: Some('['), alt: None},
+            (6, 5) => ScanCode{key: Some('h'), shift: Some('H'), hold: Some(']'), alt: None},
+            (6, 6) => ScanCode{key: Some('j'), shift: Some('J'), hold: Some('<'), alt: None},
+            (6, 7) => ScanCode{key: Some('k'), shift: Some('K'), hold: Some('>'), alt: None},
+            (6, 8) => ScanCode{key: Some('l'), shift: Some('L'), hold: Some('{'), alt: None},
+            (6, 9) => ScanCode{key: Some(';'), shift: Some(':'), hold: Some('}'), alt: None},
+
+            (3, 0) => ScanCode{key: Some('z'), shift: Some('Z'), hold: Some('_'), alt: None},
+            (3, 1) => ScanCode{key: Some('x'), shift: Some('X'), hold: Some('$'), alt: None},
+            (3, 2) => ScanCode{key: Some('c'), shift: Some('C'), hold: Some('"'), alt: None},
+            (3, 3) => ScanCode{key: Some('v'), shift: Some('V'), hold: Some(':'), alt: None},
+            (3, 4) => ScanCode{key: Some('b'), shift: Some('B'), hold: Some(';'), alt: None},
+            (7, 5) => ScanCode{key: Some('n'), shift: Some('N'), hold: Some('/'), alt: None},
+            (7, 6) => ScanCode{key: Some('m'), shift: Some('M'), hold: Some('^'), alt: None},
+            (7, 7) => ScanCode{key: Some(','), shift: Some('<'), hold: Some('='), alt: None},
+            (7, 8) => ScanCode{key: Some('.'), shift: Some('>'), hold: Some('%'), alt: None},
+
+            _ => ScanCode {key: None, shift: None, hold: None, alt: None},
+        }
+    }
+}
+
+/// kept for existing callers (e.g. the REPL's raw keyup debug readout) that
+/// want the Dvorak table directly without going through a `KeyManager`
+pub fn map_dvorak(code: (usize, usize)) -> ScanCode {
+    DvorakKeyMap.lookup(code)
+}
\ No newline at end of file
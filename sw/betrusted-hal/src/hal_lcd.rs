@@ -2,9 +2,10 @@
 
 pub mod hal_lcd {
     extern crate embedded_graphics;
-    use embedded_graphics::drawable::Pixel;
-    use embedded_graphics::geometry::Size;
+    use embedded_graphics::drawable::{Drawable, Pixel};
+    use embedded_graphics::geometry::{Point, Size};
     use embedded_graphics::pixelcolor::{BinaryColor};
+    use embedded_graphics::primitives::Rectangle;
     use embedded_graphics::DrawTarget;
     use spin::Mutex;
     use core::ops::Deref;
@@ -12,27 +13,40 @@ pub mod hal_lcd {
     /// FIXME: figure out a way to get LCD_FB mapped to the _lcdfb symbol without crashing RLS
     const LCD_FB: *mut [u32; FB_SIZE] = 0xB000_0000 as *mut [u32; FB_SIZE];
     const FB_WIDTH_WORDS: usize = 11;
-    const FB_WIDTH_PIXELS: usize = 336;
-    const FB_LINES: usize = 536;
+    /// the panel's real pixel dimensions -- `pub(crate)` so `qoi`'s decoder
+    /// can bounds-check a blob's header against them before allocating or
+    /// drawing, instead of guessing at a duplicate copy of these numbers
+    pub(crate) const FB_WIDTH_PIXELS: usize = 336;
+    pub(crate) const FB_LINES: usize = 536;
     const FB_SIZE: usize = FB_WIDTH_WORDS * FB_LINES; // 44 bytes by 536 lines
         
     /// BetrustedDisplay abstraction for embedded-graphics library
     pub struct BetrustedDisplay {
             interface: betrusted_pac::Peripherals,
+            /// copy of the pixel words as of the last `flush_dirty()`, so a
+            /// scanline that was merely touched by a draw call (and so carries
+            /// the hardware's per-line dirty bit) can be told apart from one
+            /// whose pixels actually changed, before spending SPI cycles on it
+            shadow: [u32; FB_SIZE],
     }
-    
+
     impl BetrustedDisplay {
         pub fn new() -> Self {
-            unsafe{ BetrustedDisplay{ interface: betrusted_pac::Peripherals::steal(), } }
+            unsafe{ BetrustedDisplay{ interface: betrusted_pac::Peripherals::steal(), shadow: [0; FB_SIZE], } }
         }
 
         pub fn init(&self, clk_mhz: u32) {
             lcd_init(&self.interface, clk_mhz);
         }
 
+        /// pushes every line marked dirty in hardware to the panel over SPI,
+        /// then clears those dirty bits. Blocks in [`lcd_wait_done`]'s `wfi`
+        /// park until the transfer finishes -- see that function's doc
+        /// comment for why this can't be a real claim/complete interrupt
+        /// wakeup the way [`crate::hal_com::com_irq_init`]'s is.
         pub fn flush(&self) -> Result<(), ()> {
             lcd_update_dirty(&self.interface);
-            while lcd_busy(&self.interface) {} // should this be blocking??
+            lcd_wait_done(&self.interface);
 
             // clear all the dirty bits, under the theory that it's time-wise cheaper on average
             // to visit every line and clear the dirty bits than it is to do an update_all()
@@ -43,7 +57,68 @@ pub mod hal_lcd {
             }
             Ok(())
         }
-        
+
+        /// like `flush` (including the same blocking `wfi`-park wait, not a
+        /// real interrupt wakeup -- see [`lcd_wait_done`]), but first filters
+        /// the hardware's per-line dirty bits
+        /// against `shadow`, a copy of what was actually last pushed to the
+        /// panel. A line redrawn with pixels identical to what's already on
+        /// screen (e.g. `Bounce` re-stamping a circle over its own old
+        /// position) carries the hardware dirty bit but has nothing to push;
+        /// clearing it here keeps `lcd_update_dirty` off scanlines that didn't
+        /// really change. Returns which lines were pushed, so a caller like
+        /// the REPL text area can skip re-laying-out a row it knows came back
+        /// unchanged.
+        pub fn flush_dirty(&mut self) -> [bool; FB_LINES] {
+            let mut changed = [false; FB_LINES];
+
+            for line in 0..FB_LINES {
+                let base = line * FB_WIDTH_WORDS;
+                let mut line_changed = false;
+                for word in 0..FB_WIDTH_WORDS - 1 {
+                    let current = unsafe { (*LCD_FB)[base + word] };
+                    if current != self.shadow[base + word] {
+                        line_changed = true;
+                    }
+                    self.shadow[base + word] = current;
+                }
+
+                // the last word packs this line's final 16 pixels into bits
+                // 0..15 alongside the hardware dirty-bit flag in bit 16 (see
+                // `clear`'s same special-casing of this word) -- mask that
+                // flag out before comparing/storing so it doesn't get
+                // mistaken for pixel data, but still catch real changes in
+                // those 16 pixels instead of skipping this word entirely
+                let last_word = base + FB_WIDTH_WORDS - 1;
+                let current_pixels = unsafe { (*LCD_FB)[last_word] } & 0x0000_FFFF;
+                if current_pixels != (self.shadow[last_word] & 0x0000_FFFF) {
+                    line_changed = true;
+                }
+                self.shadow[last_word] = current_pixels;
+
+                if line_changed {
+                    changed[line] = true;
+                } else {
+                    // pixels match what's already on the panel -- clear the
+                    // embedded dirty bit so lcd_update_dirty skips this line
+                    unsafe {
+                        (*LCD_FB)[base + FB_WIDTH_WORDS - 1] &= !0x0001_0000;
+                    }
+                }
+            }
+
+            lcd_update_dirty(&self.interface);
+            lcd_wait_done(&self.interface);
+
+            for lines in 0..FB_LINES {
+                unsafe {
+                    (*LCD_FB)[lines * FB_WIDTH_WORDS + (FB_WIDTH_WORDS - 1)] &= 0x0000_FFFF;
+                }
+            }
+
+            changed
+        }
+
         pub fn clear(&self) {
             let mut line_dirty: bool = false;
             for words in 0..FB_SIZE {
@@ -137,7 +212,7 @@ pub mod hal_lcd {
             }
         }
         lcd_update_all(p); // because we force an all update here
-        while lcd_busy(p) {}
+        lcd_wait_done(p);
     }
 
     pub fn lcd_test_pattern(p: &betrusted_pac::Peripherals, pattern: u32) {
@@ -149,7 +224,7 @@ pub mod hal_lcd {
             }
         }
         lcd_update_dirty(p);
-        while lcd_busy(p) {}
+        lcd_wait_done(p);
     }
 
     pub fn lcd_update_all(p: &betrusted_pac::Peripherals) {
@@ -172,6 +247,55 @@ pub mod hal_lcd {
         }
     }
 
+    /// registers MEMLCD with [`crate::irq`], mirroring [`crate::hal_com::com_irq_init`]
+    /// and [`crate::hal_kbd::kbd_irq_init`]'s shape for consistency -- but unlike
+    /// those two, this is a no-op today. MEMLCD has no `ev_pending`/`ev_enable`
+    /// CSR pair in `betrusted_pac`, so there's no hardware event for an ISR to
+    /// ack or a source for [`crate::irq::set_pending`] to claim; [`lcd_wait_done`] still
+    /// has to poll `MEMLCD.busy` directly. This stays here, named the same way
+    /// the other two HALs' init functions are, so that once the gateware grows
+    /// a real completion IRQ for MEMLCD, the registration lands in the one place
+    /// a caller already expects to find it instead of requiring every call site
+    /// that currently calls [`lcd_wait_done`] to be tracked down and rewired.
+    pub fn lcd_irq_init(_p: &betrusted_pac::Peripherals) {}
+
+    /// park the core in `wfi` between checks of `MEMLCD.busy`, instead of
+    /// spinning tight, while an update is in flight. MEMLCD doesn't expose an
+    /// `ev_pending`/`ev_enable` pair the way COM does, so there's no event to
+    /// claim through [`crate::irq`] here yet (see [`lcd_irq_init`]) -- this
+    /// just lets whatever interrupt wakes the core do so instead of burning
+    /// cycles, and keeps re-checking the real busy bit either way.
+    fn lcd_wait_done(p: &betrusted_pac::Peripherals) {
+        while lcd_busy(p) {
+            unsafe {
+                riscv::asm::wfi();
+            }
+        }
+    }
+
+    /// draw a progress bar across the bottom of the display, `percent` (0..=100)
+    /// full. Meant for a firmware update to call as its progress callback;
+    /// doesn't call `flush` itself, since the caller likely wants to batch the
+    /// bar with other redraws before pushing a frame to the panel.
+    pub fn draw_progress(display: &mut BetrustedDisplay, percent: u8) {
+        let percent = if percent > 100 { 100 } else { percent };
+        let margin: i32 = 10;
+        let y: i32 = FB_LINES as i32 - 20;
+        let width: i32 = FB_WIDTH_PIXELS as i32 - 2 * margin;
+        let filled: i32 = (width * percent as i32) / 100;
+
+        Rectangle::new(Point::new(margin, y), Point::new(margin + width, y + 10))
+            .stroke_color(Some(BinaryColor::On))
+            .draw(display);
+
+        if filled > 0 {
+            Rectangle::new(Point::new(margin, y), Point::new(margin + filled, y + 10))
+                .stroke_color(Some(BinaryColor::On))
+                .fill_color(Some(BinaryColor::On))
+                .draw(display);
+        }
+    }
+
     pub fn lcd_lines() -> u32 {
         FB_LINES as u32
     }
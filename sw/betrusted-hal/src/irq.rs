@@ -0,0 +1,129 @@
+//! PLIC-style interrupt controller abstraction: per-source handler registration,
+//! priority, enable/disable, and a claim/complete cycle, driven from a top-level
+//! trap entry that dispatches to whichever registered source is pending.
+//!
+//! There isn't a real PLIC peripheral wired into `betrusted_pac` yet -- today's
+//! litex peripherals each expose their own `ev_pending`/`ev_enable` CSR pair (see
+//! how `main.rs` drives `UART.ev_pending`/`ev_enable` already), and a HAL's own
+//! ISR calls [`set_pending`] in place of a hardware claim register read. Once the
+//! SoC grows an actual PLIC, only `set_pending`/[`claim`] need to change to talk
+//! to it; `register`/`enable`/`wait_for_irq` all keep working as-is.
+
+#![allow(dead_code)]
+
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+
+/// number of interrupt sources this table has room for
+pub const NUM_IRQS: usize = 32;
+
+type Handler = Box<dyn FnMut() + Send>;
+
+struct Source {
+    handler: Option<Handler>,
+    priority: u8,
+    enabled: bool,
+}
+
+const EMPTY_SOURCE: Source = Source { handler: None, priority: 0, enabled: false };
+
+struct Plic {
+    sources: [Source; NUM_IRQS],
+    pending: u32,
+}
+
+static PLIC: Mutex<Plic> = Mutex::new(Plic {
+    sources: [EMPTY_SOURCE; NUM_IRQS],
+    pending: 0,
+});
+
+/// set by [`set_pending`] any time a source fires, so [`wait_for_irq`] knows
+/// whether it was woken for a real reason or a spurious `wfi` return
+static IRQ_SEEN: AtomicBool = AtomicBool::new(false);
+
+/// register `handler` to run for interrupt source `irq`. `priority` breaks ties
+/// when more than one source is pending at claim time (higher runs first).
+/// Replaces any handler previously registered for this source; does not change
+/// whether the source is currently enabled.
+pub fn register(irq: usize, priority: u8, handler: impl FnMut() + Send + 'static) {
+    let mut plic = PLIC.lock();
+    let enabled = plic.sources[irq].enabled;
+    plic.sources[irq] = Source { handler: Some(Box::new(handler)), priority, enabled };
+}
+
+/// allow `irq` to be claimed at dispatch time
+pub fn enable(irq: usize) {
+    PLIC.lock().sources[irq].enabled = true;
+}
+
+/// stop `irq` from being claimed, without discarding its handler
+pub fn disable(irq: usize) {
+    PLIC.lock().sources[irq].enabled = false;
+}
+
+pub fn set_priority(irq: usize, priority: u8) {
+    PLIC.lock().sources[irq].priority = priority;
+}
+
+/// mark `irq` pending. A HAL's interrupt handler calls this once it has
+/// acknowledged its own peripheral's event-pending CSR, handing off the actual
+/// work to the registered handler via [`dispatch`].
+pub fn set_pending(irq: usize) {
+    PLIC.lock().pending |= 1 << irq;
+    IRQ_SEEN.store(true, Ordering::Release);
+}
+
+/// claim the highest-priority enabled+pending source, if any, clearing its
+/// pending bit (the "claim" half of claim/complete)
+fn claim() -> Option<usize> {
+    let mut plic = PLIC.lock();
+    let mut best: Option<(usize, u8)> = None;
+    for irq in 0..NUM_IRQS {
+        if (plic.pending & (1 << irq)) != 0 && plic.sources[irq].enabled {
+            if best.map_or(true, |(_, p)| plic.sources[irq].priority > p) {
+                best = Some((irq, plic.sources[irq].priority));
+            }
+        }
+    }
+    if let Some((irq, _)) = best {
+        plic.pending &= !(1 << irq);
+    }
+    best.map(|(irq, _)| irq)
+}
+
+/// run the handler for `irq` (the "complete" half of claim/complete)
+fn complete(irq: usize) {
+    let mut handler = PLIC.lock().sources[irq].handler.take();
+    if let Some(h) = handler.as_mut() {
+        h();
+    }
+    PLIC.lock().sources[irq].handler = handler;
+}
+
+/// claim and run every currently pending, enabled source, highest priority
+/// first; called from the top-level trap entry below
+pub fn dispatch() {
+    while let Some(irq) = claim() {
+        complete(irq);
+    }
+}
+
+/// block the core until [`set_pending`] has marked at least one source pending
+/// since this call started, parking in `wfi` between checks so idle time actually sleeps
+/// the core instead of spinning
+pub fn wait_for_irq() {
+    IRQ_SEEN.store(false, Ordering::Release);
+    while !IRQ_SEEN.load(Ordering::Acquire) {
+        unsafe {
+            riscv::asm::wfi();
+        }
+    }
+}
+
+/// top-level machine-external-interrupt trap entry. `riscv-rt` calls this by
+/// name (it's a weak symbol) whenever an external interrupt line fires.
+#[no_mangle]
+pub extern "C" fn MachineExternal() {
+    dispatch();
+}
@@ -1,6 +1,9 @@
 #![no_std]
 
 extern crate alloc;
+extern crate riscv;
+extern crate spin;
+extern crate efuse_ecc;
 
 pub mod hal_i2c;
 pub mod hal_time;
@@ -9,6 +12,15 @@ pub mod hal_com;
 pub mod hal_kbd;
 pub mod hal_uart;
 pub mod hal_xadc;
+pub mod irq;
+pub mod fw_update;
+pub mod log;
+pub mod config_store;
+pub mod qoi;
+pub mod ttf;
+pub mod plot;
+pub mod cursor;
+pub mod text_layout;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,116 @@
+//! A small `spin::Mutex`-guarded logging facade over the UART, so two call
+//! sites (including an interrupt handler) can't interleave their bytes the
+//! way two direct `p.UART.rxtx` writers would. Doesn't touch `alloc` at all,
+//! so `print!`/`println!`/the leveled macros are safe to call before the
+//! heap is initialized -- early boot and any OOM-handling path can still log.
+
+use core::fmt::{self, Write};
+use spin::Mutex;
+
+/// log severity, most severe first. [`MIN_LEVEL`] is the highest (most
+/// verbose) level compiled in; bump it down to `Level::Warn` or `Level::Error`
+/// for a release build to have the compiler fold away everything noisier --
+/// the comparison against `MIN_LEVEL` is a `u8` constant compare, so a call
+/// below the floor costs nothing beyond the `if` itself, and its
+/// `format_args!` is never evaluated.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+}
+
+impl Level {
+    pub fn tag(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+        }
+    }
+}
+
+/// compile-time floor for the leveled macros; see [`Level`]
+pub const MIN_LEVEL: Level = Level::Info;
+
+/// writes straight to the UART's `rxtx`/`txfull` CSRs, the same way the
+/// "loop" REPL command in `main.rs` does -- there's no `hal_uart` HAL in this
+/// tree to layer on top of yet.
+struct UartWriter;
+
+impl Write for UartWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let p = unsafe { betrusted_pac::Peripherals::steal() };
+        for byte in s.bytes() {
+            while p.UART.txfull.read().bits() != 0 {}
+            unsafe { p.UART.rxtx.write(|w| w.bits(byte as u32)); }
+        }
+        Ok(())
+    }
+}
+
+static UART_LOCK: Mutex<UartWriter> = Mutex::new(UartWriter);
+
+/// used by [`print!`]; not meant to be called directly
+pub fn _print(args: fmt::Arguments) {
+    let _ = UART_LOCK.lock().write_fmt(args);
+}
+
+/// used by [`println!`]; not meant to be called directly
+pub fn _println(args: fmt::Arguments) {
+    let mut w = UART_LOCK.lock();
+    let _ = w.write_fmt(args);
+    let _ = w.write_str("\r\n");
+}
+
+/// used by [`error!`]/[`warn!`]/[`info!`]; not meant to be called directly.
+/// Takes the whole line's lock once, so the tag, message, and line ending
+/// can't be split apart by a concurrent logger.
+pub fn _log_line(tag: &str, args: fmt::Arguments) {
+    let mut w = UART_LOCK.lock();
+    let _ = write!(w, "[{}] ", tag);
+    let _ = w.write_fmt(args);
+    let _ = w.write_str("\r\n");
+}
+
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::log::_print(core::format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! println {
+    ($($arg:tt)*) => {
+        $crate::log::_println(core::format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        if ($crate::log::Level::Error as u8) <= ($crate::log::MIN_LEVEL as u8) {
+            $crate::log::_log_line($crate::log::Level::Error.tag(), core::format_args!($($arg)*));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        if ($crate::log::Level::Warn as u8) <= ($crate::log::MIN_LEVEL as u8) {
+            $crate::log::_log_line($crate::log::Level::Warn.tag(), core::format_args!($($arg)*));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if ($crate::log::Level::Info as u8) <= ($crate::log::MIN_LEVEL as u8) {
+            $crate::log::_log_line($crate::log::Level::Info.tag(), core::format_args!($($arg)*));
+        }
+    };
+}
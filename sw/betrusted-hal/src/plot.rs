@@ -0,0 +1,197 @@
+//! Reusable autoscaling scrolling time-series widget. Generalizes what used
+//! to be an inline double loop hardwired to exactly 300 noise samples, a
+//! fixed `/32` vertical divisor, and two fixed row offsets -- any number of
+//! named traces of any ring-buffer capacity can now share one plot, each
+//! autoscaled to its own (or a shared) running min/max, with grid lines and
+//! numeric labels along the left margin.
+
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::vec::Vec;
+use embedded_graphics::fonts::Font8x16;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Line;
+use embedded_graphics::primitives::Rectangle;
+
+use crate::hal_lcd::hal_lcd::BetrustedDisplay;
+
+/// one named, fixed-capacity ring buffer of samples
+struct Trace {
+    name: &'static str,
+    samples: VecDeque<i32>,
+    capacity: usize,
+}
+
+impl Trace {
+    fn push(&mut self, sample: i32) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// running min/max over the visible window -- `None` once nothing has
+    /// been pushed yet, rather than a false `(0, 0)` range
+    fn min_max(&self) -> Option<(i32, i32)> {
+        let mut iter = self.samples.iter();
+        let first = *iter.next()?;
+        let mut min = first;
+        let mut max = first;
+        for &s in iter {
+            if s < min {
+                min = s;
+            }
+            if s > max {
+                max = s;
+            }
+        }
+        Some((min, max))
+    }
+}
+
+/// a scrolling, autoscaling multi-trace plot. Owns its traces' ring buffers
+/// -- the caller calls `push` as new samples arrive (e.g. once per
+/// `noise_task` round) and `draw` once per frame, the same two-step split as
+/// `hal_lcd`'s draw-then-flush.
+pub struct TimeSeriesPlot {
+    traces: Vec<Trace>,
+}
+
+impl TimeSeriesPlot {
+    pub fn new() -> Self {
+        TimeSeriesPlot { traces: Vec::new() }
+    }
+
+    /// registers a new trace, returning the id `push` takes
+    pub fn add_trace(&mut self, name: &'static str, capacity: usize) -> usize {
+        self.traces.push(Trace { name, samples: VecDeque::with_capacity(capacity), capacity });
+        self.traces.len() - 1
+    }
+
+    pub fn push(&mut self, trace_id: usize, sample: i32) {
+        self.traces[trace_id].push(sample);
+    }
+
+    fn combined_min_max(&self) -> Option<(i32, i32)> {
+        self.traces.iter().filter_map(Trace::min_max).fold(None, |acc, (lo, hi)| match acc {
+            None => Some((lo, hi)),
+            Some((accum_lo, accum_hi)) => Some((accum_lo.min(lo), accum_hi.max(hi))),
+        })
+    }
+
+    /// draws every registered trace stacked top-to-bottom inside `bounds`,
+    /// each getting an equal share of the height. `shared_axis` picks
+    /// whether every trace scales against the combined min/max (so traces
+    /// are visually comparable to each other) or each against its own (so a
+    /// trace with a much smaller range doesn't flatten to a line).
+    pub fn draw(&self, display: &mut BetrustedDisplay, bounds: Rectangle<BinaryColor>, shared_axis: bool) {
+        if self.traces.is_empty() {
+            return;
+        }
+
+        let left = bounds.top_left.x;
+        let top = bounds.top_left.y;
+        let width = bounds.bottom_right.x - bounds.top_left.x;
+        let total_height = bounds.bottom_right.y - bounds.top_left.y;
+        let slot_height = total_height / self.traces.len() as i32;
+        let shared_min_max = if shared_axis { self.combined_min_max() } else { None };
+
+        for (i, trace) in self.traces.iter().enumerate() {
+            let slot_top = top + slot_height * i as i32;
+            let slot_bottom = slot_top + slot_height;
+            let min_max = shared_min_max.or_else(|| trace.min_max());
+            draw_trace(display, trace, left, slot_top, slot_bottom, width, min_max);
+        }
+    }
+}
+
+/// a "nice" grid step (1/2/5 x a power of ten) that divides `range` into
+/// roughly `target_ticks` intervals, the way a spreadsheet chart picks axis
+/// ticks instead of showing an ugly, arbitrary-looking spacing
+fn nice_step(range: i32, target_ticks: i32) -> i32 {
+    let range = range.max(1);
+    let raw_step = (range / target_ticks.max(1)).max(1);
+    let mut magnitude = 1;
+    while magnitude * 10 <= raw_step {
+        magnitude *= 10;
+    }
+    for candidate in [1, 2, 5, 10] {
+        if candidate * magnitude >= raw_step {
+            return candidate * magnitude;
+        }
+    }
+    10 * magnitude
+}
+
+fn draw_trace(
+    display: &mut BetrustedDisplay,
+    trace: &Trace,
+    left: i32,
+    top: i32,
+    bottom: i32,
+    width: i32,
+    min_max: Option<(i32, i32)>,
+) {
+    // axes
+    Line::<BinaryColor>::new(Point::new(left, top), Point::new(left, bottom))
+        .stroke_color(Some(BinaryColor::On))
+        .draw(display);
+    Line::<BinaryColor>::new(Point::new(left, bottom), Point::new(left + width, bottom))
+        .stroke_color(Some(BinaryColor::On))
+        .draw(display);
+
+    Font8x16::render_str(trace.name)
+        .stroke_color(Some(BinaryColor::On))
+        .translate(Point::new(left + 2, top))
+        .draw(display);
+
+    let (min, max) = match min_max {
+        Some(range) => range,
+        None => return, // nothing pushed yet -- just the empty axes
+    };
+
+    // grid lines at a "nice" value step, each labeled on the left margin
+    let step = nice_step((max - min).max(1), 4);
+    let mut tick = (min / step) * step;
+    while tick <= max {
+        if tick >= min {
+            let y = sample_to_y(tick, min, max, top, bottom);
+            Line::<BinaryColor>::new(Point::new(left, y), Point::new(left + width, y))
+                .stroke_color(Some(BinaryColor::On))
+                .draw(display);
+            Font8x16::render_str(&format!("{}", tick))
+                .stroke_color(Some(BinaryColor::On))
+                .translate(Point::new(left - 16, y - 8))
+                .draw(display);
+        }
+        tick += step;
+    }
+
+    // the trace itself
+    let len = trace.samples.len();
+    if len < 2 {
+        return;
+    }
+    for (i, (&a, &b)) in trace.samples.iter().zip(trace.samples.iter().skip(1)).enumerate() {
+        let x0 = left + (i as i32 * width) / (len as i32 - 1);
+        let x1 = left + ((i as i32 + 1) * width) / (len as i32 - 1);
+        let y0 = sample_to_y(a, min, max, top, bottom);
+        let y1 = sample_to_y(b, min, max, top, bottom);
+        Line::<BinaryColor>::new(Point::new(x0, y0), Point::new(x1, y1))
+            .stroke_color(Some(BinaryColor::On))
+            .draw(display);
+    }
+}
+
+/// `y = bottom - (sample - min) * height / (max - min)`, centering the
+/// trace in its slot instead of dividing by zero when every visible sample
+/// is identical (a flat line, e.g. the noise generator is off)
+fn sample_to_y(sample: i32, min: i32, max: i32, top: i32, bottom: i32) -> i32 {
+    if max == min {
+        return (top + bottom) / 2;
+    }
+    let height = bottom - top;
+    bottom - (sample - min) * height / (max - min)
+}
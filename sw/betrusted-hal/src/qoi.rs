@@ -0,0 +1,216 @@
+//! Self-contained decoder for QOI (Quite OK Image) blobs, blitted onto the
+//! monochrome `hal_lcd` framebuffer with Floyd-Steinberg error-diffusion
+//! dithering so a greyscale logo, status icon, or captured frame still reads
+//! on the 1-bit panel. No bulky codec crate is available in this `no_std`
+//! tree, so the format (header + the handful of per-pixel ops) is decoded by
+//! hand here, same spirit as `config_store`'s own record format.
+
+use crate::hal_lcd::hal_lcd::{BetrustedDisplay, FB_LINES, FB_WIDTH_PIXELS};
+use alloc::vec::Vec;
+use embedded_graphics::drawable::Pixel;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::DrawTarget;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum QoiError {
+    /// blob is shorter than the 14-byte header, or truncated mid-stream
+    Truncated,
+    /// the first 4 bytes aren't `qoif`
+    BadMagic,
+    /// header's `width`/`height` don't fit the real 336x536 panel, or imply
+    /// a pixel count no encoding of a blob this short could actually
+    /// produce -- rejected before the pixel buffer is allocated
+    TooLarge,
+}
+
+const HEADER_LEN: usize = 14;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+/// QOI's run-length op (`QOI_OP_RUN`) can encode at most this many repeats
+/// of one pixel per byte -- used to sanity-bound how many pixels a blob of
+/// a given length could possibly decode to, so a garbage header can't drive
+/// a multi-gigabyte `Vec::with_capacity` before the decode loop ever gets a
+/// chance to notice the data is truncated
+const MAX_PIXELS_PER_BYTE: usize = 62;
+
+const QOI_OP_RGB: u8 = 0xFE;
+const QOI_OP_RGBA: u8 = 0xFF;
+const QOI_OP_INDEX: u8 = 0x00;
+const QOI_OP_DIFF: u8 = 0x40;
+const QOI_OP_LUMA: u8 = 0x80;
+const QOI_OP_RUN: u8 = 0xC0;
+const TAG_MASK: u8 = 0xC0;
+
+#[derive(Clone, Copy, Default)]
+struct Rgba {
+    r: u8,
+    g: u8,
+    b: u8,
+    a: u8,
+}
+
+fn hash_index(p: Rgba) -> usize {
+    ((p.r as usize * 3 + p.g as usize * 5 + p.b as usize * 7 + p.a as usize * 11) % 64) as usize
+}
+
+/// decodes a QOI blob into `(width, height, luminance)`, one
+/// `(77*r + 150*g + 29*b) >> 8` byte per pixel, row-major
+pub fn decode_luminance(data: &[u8]) -> Result<(u32, u32, Vec<u8>), QoiError> {
+    if data.len() < HEADER_LEN + END_MARKER.len() {
+        return Err(QoiError::Truncated);
+    }
+    if &data[0..4] != b"qoif" {
+        return Err(QoiError::BadMagic);
+    }
+    let width = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+    let height = u32::from_be_bytes([data[8], data[9], data[10], data[11]]);
+    if width as usize > FB_WIDTH_PIXELS || height as usize > FB_LINES {
+        return Err(QoiError::TooLarge);
+    }
+    let pixel_count = (width as usize).saturating_mul(height as usize);
+    let max_decodable_pixels = (data.len() - HEADER_LEN).saturating_mul(MAX_PIXELS_PER_BYTE);
+    if pixel_count > max_decodable_pixels {
+        return Err(QoiError::TooLarge);
+    }
+
+    let mut seen = [Rgba::default(); 64];
+    let mut prev = Rgba { r: 0, g: 0, b: 0, a: 0xFF };
+    let mut luminance = Vec::with_capacity(pixel_count);
+    let mut pos = HEADER_LEN;
+
+    while luminance.len() < pixel_count {
+        if pos >= data.len() {
+            return Err(QoiError::Truncated);
+        }
+        let byte = data[pos];
+        let pixel = if byte == QOI_OP_RGB {
+            if pos + 3 >= data.len() {
+                return Err(QoiError::Truncated);
+            }
+            let p = Rgba { r: data[pos + 1], g: data[pos + 2], b: data[pos + 3], a: prev.a };
+            pos += 4;
+            p
+        } else if byte == QOI_OP_RGBA {
+            if pos + 4 >= data.len() {
+                return Err(QoiError::Truncated);
+            }
+            let p = Rgba { r: data[pos + 1], g: data[pos + 2], b: data[pos + 3], a: data[pos + 4] };
+            pos += 5;
+            p
+        } else {
+            match byte & TAG_MASK {
+                QOI_OP_INDEX => {
+                    pos += 1;
+                    seen[(byte & 0x3F) as usize]
+                }
+                QOI_OP_DIFF => {
+                    let dr = ((byte >> 4) & 0x03) as i8 - 2;
+                    let dg = ((byte >> 2) & 0x03) as i8 - 2;
+                    let db = (byte & 0x03) as i8 - 2;
+                    pos += 1;
+                    Rgba {
+                        r: prev.r.wrapping_add(dr as u8),
+                        g: prev.g.wrapping_add(dg as u8),
+                        b: prev.b.wrapping_add(db as u8),
+                        a: prev.a,
+                    }
+                }
+                QOI_OP_LUMA => {
+                    if pos + 1 >= data.len() {
+                        return Err(QoiError::Truncated);
+                    }
+                    let dg = (byte & 0x3F) as i8 - 32;
+                    let byte2 = data[pos + 1];
+                    let dr_dg = ((byte2 >> 4) & 0x0F) as i8 - 8;
+                    let db_dg = (byte2 & 0x0F) as i8 - 8;
+                    let dr = dg.wrapping_add(dr_dg);
+                    let db = dg.wrapping_add(db_dg);
+                    pos += 2;
+                    Rgba {
+                        r: prev.r.wrapping_add(dr as u8),
+                        g: prev.g.wrapping_add(dg as u8),
+                        b: prev.b.wrapping_add(db as u8),
+                        a: prev.a,
+                    }
+                }
+                QOI_OP_RUN => {
+                    let run = (byte & 0x3F) as usize + 1;
+                    pos += 1;
+                    for _ in 0..run {
+                        if luminance.len() >= pixel_count {
+                            break;
+                        }
+                        luminance.push(luma(prev));
+                    }
+                    continue;
+                }
+                _ => unreachable!("2-bit tag covers all remaining byte values"),
+            }
+        };
+
+        seen[hash_index(pixel)] = pixel;
+        prev = pixel;
+        luminance.push(luma(pixel));
+    }
+
+    Ok((width, height, luminance))
+}
+
+fn luma(p: Rgba) -> u8 {
+    ((77 * p.r as u32 + 150 * p.g as u32 + 29 * p.b as u32) >> 8) as u8
+}
+
+/// Floyd-Steinberg-dithers a luminance buffer to 1-bit and blits it into
+/// `display` at `origin`. Error is tracked in a signed row buffer rather than
+/// mutating `luminance`, so the caller's decoded image stays reusable.
+pub fn dither_blit(display: &mut BetrustedDisplay, origin: Point, width: u32, height: u32, luminance: &[u8]) {
+    let w = width as usize;
+    let h = height as usize;
+    let mut error: Vec<i32> = Vec::with_capacity(w * h);
+    error.resize(w * h, 0i32);
+
+    for y in 0..h {
+        for x in 0..w {
+            let idx = y * w + x;
+            let gray = luminance[idx] as i32 + error[idx];
+            let gray = gray.max(0).min(255);
+            let on = gray >= 128;
+            let quant_error = gray - if on { 255 } else { 0 };
+
+            let color = if on { BinaryColor::On } else { BinaryColor::Off };
+            let coord = Point::new(origin.x + x as i32, origin.y + y as i32);
+            display.draw_pixel(Pixel(coord, color));
+
+            if x + 1 < w {
+                error[idx + 1] += quant_error * 7 / 16;
+            }
+            if y + 1 < h {
+                if x > 0 {
+                    error[idx + w - 1] += quant_error * 3 / 16;
+                }
+                error[idx + w] += quant_error * 5 / 16;
+                if x + 1 < w {
+                    error[idx + w + 1] += quant_error * 1 / 16;
+                }
+            }
+        }
+    }
+}
+
+/// decodes `data` and blits it into `display` at `origin` in one call --
+/// the usual entry point, mirroring `hal_lcd::draw_progress`'s shape
+pub fn draw_qoi(display: &mut BetrustedDisplay, origin: Point, data: &[u8]) -> Result<(), QoiError> {
+    let (width, height, luminance) = decode_luminance(data)?;
+
+    let panel_size = display.size();
+    if origin.x < 0
+        || origin.y < 0
+        || origin.x as u32 + width > panel_size.width
+        || origin.y as u32 + height > panel_size.height
+    {
+        return Err(QoiError::TooLarge);
+    }
+
+    dither_blit(display, origin, width, height, &luminance);
+    Ok(())
+}
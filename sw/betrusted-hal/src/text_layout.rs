@@ -0,0 +1,103 @@
+//! Unicode-width-aware layout for REPL scrollback, so a long line or a
+//! wide/CJK character doesn't silently run off the right edge of the
+//! display. Wraps or truncates a string against a column count measured in
+//! fixed-width character cells, using a simplified East-Asian-width table
+//! to count common wide characters (CJK ideographs, Hangul, fullwidth
+//! forms, ...) as two cells instead of one.
+//!
+//! This tree has no `unicode-segmentation` crate (no Cargo.toml, no
+//! vendored deps at all), so there's no real extended-grapheme-cluster
+//! support here -- a base character plus a combining mark still lays out
+//! as two cells rather than merging into one. What it does guarantee is
+//! that splits only ever happen on `char` (Unicode scalar value)
+//! boundaries, so UTF-8 is never cut mid-byte.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// how many fixed-width character cells `ch` occupies when rendered.
+/// Covers the commonly-hit wide ranges (CJK, Hangul, fullwidth forms) --
+/// not the full UAX #11 East Asian Width table.
+pub fn char_width(ch: char) -> usize {
+    let c = ch as u32;
+    let wide = matches!(c,
+        0x1100..=0x115F     // Hangul Jamo
+        | 0x2E80..=0x303E   // CJK radicals, Kangxi radicals, CJK symbols/punctuation
+        | 0x3041..=0x33FF   // Hiragana, Katakana, CJK compatibility
+        | 0x3400..=0x4DBF   // CJK unified ideographs extension A
+        | 0x4E00..=0x9FFF   // CJK unified ideographs
+        | 0xA000..=0xA4CF   // Yi syllables/radicals
+        | 0xAC00..=0xD7A3   // Hangul syllables
+        | 0xF900..=0xFAFF   // CJK compatibility ideographs
+        | 0xFF00..=0xFF60   // fullwidth forms
+        | 0xFFE0..=0xFFE6   // fullwidth signs
+        | 0x20000..=0x3FFFD // CJK unified ideographs extension B and beyond
+    );
+    if wide {
+        2
+    } else {
+        1
+    }
+}
+
+/// total cell width of `s`, the sum of each `char`'s `char_width`
+pub fn string_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// one wrapped row: the text it holds and that text's cell width
+pub struct LaidOutLine {
+    pub text: String,
+    pub width: usize,
+}
+
+/// soft-wraps `text` onto as many rows as needed to keep each row within
+/// `column_width` cells, splitting only on whole `char`s. Always returns at
+/// least one row, even for an empty string.
+pub fn wrap(text: &str, column_width: usize) -> Vec<LaidOutLine> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0;
+
+    for ch in text.chars() {
+        let w = char_width(ch);
+        if current_width + w > column_width && !current.is_empty() {
+            lines.push(LaidOutLine { text: current.clone(), width: current_width });
+            current.clear();
+            current_width = 0;
+        }
+        current.push(ch);
+        current_width += w;
+    }
+    lines.push(LaidOutLine { text: current, width: current_width });
+    lines
+}
+
+/// truncates `text` to fit within `column_width` cells, replacing the
+/// cut-off tail with "..." if anything had to go -- for single-line fields
+/// (the command/input rows) where wrapping isn't appropriate.
+pub fn truncate_with_ellipsis(text: &str, column_width: usize) -> String {
+    if string_width(text) <= column_width {
+        return String::from(text);
+    }
+
+    const ELLIPSIS: &str = "...";
+    let ellipsis_width = string_width(ELLIPSIS);
+    if column_width <= ellipsis_width {
+        return String::from(&ELLIPSIS[..column_width.min(ELLIPSIS.len())]);
+    }
+
+    let budget = column_width - ellipsis_width;
+    let mut out = String::new();
+    let mut width = 0;
+    for ch in text.chars() {
+        let w = char_width(ch);
+        if width + w > budget {
+            break;
+        }
+        out.push(ch);
+        width += w;
+    }
+    out.push_str(ELLIPSIS);
+    out
+}
@@ -0,0 +1,583 @@
+//! Minimal TrueType outline parser and rasterizer, so a debug console can lay
+//! out text at a runtime-chosen pixel size instead of being locked to the
+//! `Font8x16`/`Font12x16` bitmap tiers and their hardcoded `line_height`
+//! constants. There's no embedded `.ttf` blob anywhere in this tree yet (no
+//! asset to link in), so nothing in `main`'s render loop switches over to
+//! this module in this change -- it's the rasterizer those call sites can
+//! build on once a font asset exists, the same way `qoi` existed before any
+//! caller decoded a real image through it.
+//!
+//! Scope: simple (non-composite) glyphs only, `cmap` format 4 (the common
+//! BMP subtable), no GPOS/kern pair tables -- glyphs lay out by advance width
+//! alone, not true kerning pairs. That covers the common case (Latin glyphs
+//! in a typical hand-built or subset `.ttf`) without a full OpenType stack.
+
+use alloc::vec::Vec;
+use embedded_graphics::drawable::Pixel;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::DrawTarget;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TtfError {
+    /// too short to even hold a table directory, or a required table is missing
+    Malformed,
+    /// no (3,1) or (0,x) `cmap` subtable, or it isn't format 4
+    UnsupportedCmap,
+}
+
+// every offset these read comes straight out of the font's own tables (table
+// directory offsets, loca entries, glyf contour/point counts...), so none of
+// them can be trusted -- a truncated or hand-corrupted `.ttf` must turn into
+// `TtfError::Malformed`, not an out-of-bounds index panic.
+fn u8_at(data: &[u8], offset: usize) -> Result<u8, TtfError> {
+    data.get(offset).copied().ok_or(TtfError::Malformed)
+}
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, TtfError> {
+    let end = offset.checked_add(2).ok_or(TtfError::Malformed)?;
+    let b = data.get(offset..end).ok_or(TtfError::Malformed)?;
+    Ok(u16::from_be_bytes([b[0], b[1]]))
+}
+fn i16_at(data: &[u8], offset: usize) -> Result<i16, TtfError> {
+    u16_at(data, offset).map(|v| v as i16)
+}
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, TtfError> {
+    let end = offset.checked_add(4).ok_or(TtfError::Malformed)?;
+    let b = data.get(offset..end).ok_or(TtfError::Malformed)?;
+    Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// per-glyph layout info, in font units (scale by `size_px / units_per_em` to get pixels)
+#[derive(Clone, Copy)]
+pub struct GlyphMetrics {
+    pub advance_width: u16,
+    pub left_side_bearing: i16,
+}
+
+/// a flattened outline -- each contour is a closed polygon, curves already
+/// subdivided into line segments, ready for scanline rasterization
+pub struct Outline {
+    pub contours: Vec<Vec<(f32, f32)>>,
+    pub advance_px: f32,
+    pub x_min_px: f32,
+    pub x_max_px: f32,
+    pub y_min_px: f32,
+    pub y_max_px: f32,
+}
+
+/// a parsed `.ttf`, borrowing its backing bytes rather than copying the font
+/// into a second buffer
+pub struct Font<'a> {
+    data: &'a [u8],
+    units_per_em: u16,
+    ascender: i16,
+    descender: i16,
+    num_glyphs: u16,
+    num_h_metrics: u16,
+    loca_long: bool,
+    cmap_offset: u32,
+    loca_offset: u32,
+    glyf_offset: u32,
+    hmtx_offset: u32,
+}
+
+impl<'a> Font<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, TtfError> {
+        if data.len() < 12 {
+            return Err(TtfError::Malformed);
+        }
+        let num_tables = u16_at(data, 4)? as usize;
+        if data.len() < 12 + num_tables * 16 {
+            return Err(TtfError::Malformed);
+        }
+
+        let mut head = None;
+        let mut hhea = None;
+        let mut maxp = None;
+        let mut cmap = None;
+        let mut loca = None;
+        let mut glyf = None;
+        let mut hmtx = None;
+
+        for i in 0..num_tables {
+            let entry = 12 + i * 16;
+            let tag = &data[entry..entry + 4];
+            let offset = u32_at(data, entry + 8)? as usize;
+            match tag {
+                b"head" => head = Some(offset),
+                b"hhea" => hhea = Some(offset),
+                b"maxp" => maxp = Some(offset),
+                b"cmap" => cmap = Some(offset),
+                b"loca" => loca = Some(offset),
+                b"glyf" => glyf = Some(offset),
+                b"hmtx" => hmtx = Some(offset),
+                _ => {}
+            }
+        }
+
+        let head = head.ok_or(TtfError::Malformed)?;
+        let hhea = hhea.ok_or(TtfError::Malformed)?;
+        let maxp = maxp.ok_or(TtfError::Malformed)?;
+        let cmap = cmap.ok_or(TtfError::Malformed)?;
+        let loca = loca.ok_or(TtfError::Malformed)?;
+        let glyf = glyf.ok_or(TtfError::Malformed)?;
+        let hmtx = hmtx.ok_or(TtfError::Malformed)?;
+
+        Ok(Font {
+            data,
+            units_per_em: u16_at(data, head + 18)?,
+            loca_long: i16_at(data, head + 50)? != 0,
+            ascender: i16_at(data, hhea + 4)?,
+            descender: i16_at(data, hhea + 6)?,
+            num_h_metrics: u16_at(data, hhea + 34)?,
+            num_glyphs: u16_at(data, maxp + 4)?,
+            cmap_offset: cmap as u32,
+            loca_offset: loca as u32,
+            glyf_offset: glyf as u32,
+            hmtx_offset: hmtx as u32,
+        })
+    }
+
+    pub fn units_per_em(&self) -> u16 {
+        self.units_per_em
+    }
+
+    /// ascender/descender in pixels at `size_px`, for baseline/line-height layout
+    pub fn ascent_px(&self, size_px: f32) -> f32 {
+        self.ascender as f32 * size_px / self.units_per_em as f32
+    }
+    pub fn descent_px(&self, size_px: f32) -> f32 {
+        self.descender as f32 * size_px / self.units_per_em as f32
+    }
+    pub fn line_height_px(&self, size_px: f32) -> f32 {
+        self.ascent_px(size_px) - self.descent_px(size_px)
+    }
+
+    /// walks the (3,1) or (0,x) format-4 `cmap` subtable; falls back to glyph 0
+    /// (`.notdef`) for anything it can't map, same as a missing-glyph box would
+    pub fn glyph_id(&self, ch: char) -> Result<u16, TtfError> {
+        let data = self.data;
+        let base = self.cmap_offset as usize;
+        let num_subtables = u16_at(data, base + 2)? as usize;
+
+        let mut subtable_offset = None;
+        for i in 0..num_subtables {
+            let entry = base + 4 + i * 8;
+            let platform_id = u16_at(data, entry)?;
+            let encoding_id = u16_at(data, entry + 2)?;
+            let offset = u32_at(data, entry + 4)? as usize;
+            if (platform_id == 3 && (encoding_id == 1 || encoding_id == 10)) || platform_id == 0 {
+                subtable_offset = Some(base + offset);
+                break;
+            }
+        }
+        let sub = subtable_offset.ok_or(TtfError::UnsupportedCmap)?;
+        if u16_at(data, sub)? != 4 {
+            return Err(TtfError::UnsupportedCmap);
+        }
+
+        let code = ch as u32;
+        if code > 0xFFFF {
+            return Ok(0);
+        }
+        let code = code as u16;
+
+        let seg_count_x2 = u16_at(data, sub + 6)? as usize;
+        let seg_count = seg_count_x2 / 2;
+        let end_codes = sub + 14;
+        let start_codes = end_codes + seg_count_x2 + 2; // +2 skips reservedPad
+        let id_deltas = start_codes + seg_count_x2;
+        let id_range_offsets = id_deltas + seg_count_x2;
+
+        for seg in 0..seg_count {
+            let end_code = u16_at(data, end_codes + seg * 2)?;
+            if code > end_code {
+                continue;
+            }
+            let start_code = u16_at(data, start_codes + seg * 2)?;
+            if code < start_code {
+                return Ok(0);
+            }
+            let id_delta = i16_at(data, id_deltas + seg * 2)?;
+            let id_range_offset = u16_at(data, id_range_offsets + seg * 2)?;
+            if id_range_offset == 0 {
+                return Ok((code as i32 + id_delta as i32) as u16);
+            }
+            let glyph_addr = id_range_offsets + seg * 2 + id_range_offset as usize + (code - start_code) as usize * 2;
+            if glyph_addr + 1 >= data.len() {
+                return Ok(0);
+            }
+            let raw_id = u16_at(data, glyph_addr)?;
+            if raw_id == 0 {
+                return Ok(0);
+            }
+            return Ok((raw_id as i32 + id_delta as i32) as u16);
+        }
+        Ok(0)
+    }
+
+    fn metrics(&self, glyph_id: u16) -> Result<GlyphMetrics, TtfError> {
+        let idx = (glyph_id as usize).min(self.num_h_metrics.saturating_sub(1) as usize);
+        let entry = self.hmtx_offset as usize + idx * 4;
+        Ok(GlyphMetrics {
+            advance_width: u16_at(self.data, entry)?,
+            left_side_bearing: i16_at(self.data, entry + 2)?,
+        })
+    }
+
+    fn glyf_range(&self, glyph_id: u16) -> Result<(usize, usize), TtfError> {
+        let data = self.data;
+        if self.loca_long {
+            let off = self.loca_offset as usize + glyph_id as usize * 4;
+            Ok((u32_at(data, off)? as usize, u32_at(data, off + 4)? as usize))
+        } else {
+            let off = self.loca_offset as usize + glyph_id as usize * 2;
+            Ok((u16_at(data, off)? as usize * 2, u16_at(data, off + 2)? as usize * 2))
+        }
+    }
+
+    /// decodes one glyph's outline and scales it to `size_px`, ready to
+    /// rasterize. Composite glyphs (accented Latin, most non-Latin scripts)
+    /// aren't decoded -- they come back as an empty outline with just the
+    /// advance width, so layout still proceeds instead of aborting the string.
+    ///
+    /// Every length and offset walked below (contour count, point count,
+    /// instruction length, repeat counts) comes straight out of the glyph's
+    /// own bytes, so a truncated or corrupted `glyf` entry is expected input,
+    /// not a logic error -- this returns `Err(TtfError::Malformed)` instead
+    /// of indexing past the end of `data`.
+    pub fn outline(&self, glyph_id: u16, size_px: f32) -> Result<Outline, TtfError> {
+        let scale = size_px / self.units_per_em as f32;
+        let metrics = self.metrics(glyph_id)?;
+        let advance_px = metrics.advance_width as f32 * scale;
+        let empty = |x_min_px, x_max_px, y_min_px, y_max_px| Outline {
+            contours: Vec::new(),
+            advance_px,
+            x_min_px,
+            x_max_px,
+            y_min_px,
+            y_max_px,
+        };
+
+        if glyph_id >= self.num_glyphs {
+            return Ok(empty(0.0, 0.0, 0.0, 0.0));
+        }
+        let (start, end) = self.glyf_range(glyph_id)?;
+        if end <= start {
+            // whitespace/empty glyph -- valid, just nothing to draw
+            return Ok(empty(0.0, 0.0, 0.0, 0.0));
+        }
+
+        let base = self.glyf_offset as usize + start;
+        let glyph_end = self.glyf_offset as usize + end;
+        let data = self.data.get(..glyph_end).ok_or(TtfError::Malformed)?;
+        let num_contours = i16_at(data, base)?;
+        let x_min = i16_at(data, base + 2)? as f32 * scale;
+        let y_min = i16_at(data, base + 4)? as f32 * scale;
+        let x_max = i16_at(data, base + 6)? as f32 * scale;
+        let y_max = i16_at(data, base + 8)? as f32 * scale;
+
+        if num_contours < 0 {
+            // composite glyph -- unsupported in this minimal parser, see doc comment
+            return Ok(empty(x_min, x_max, y_min, y_max));
+        }
+        let num_contours = num_contours as usize;
+
+        // nothing below may read at or past `glyph_end` -- that's the limit
+        // the `loca` table itself promised this glyph's data stays within
+        let in_bounds = |pos: usize, len: usize| pos.checked_add(len).map_or(false, |e| e <= glyph_end);
+
+        let mut pos = base + 10;
+        let mut end_pts = Vec::with_capacity(num_contours);
+        for _ in 0..num_contours {
+            end_pts.push(u16_at(data, pos)? as usize);
+            pos += 2;
+        }
+        let num_points = end_pts.last().map(|e| e + 1).unwrap_or(0);
+
+        let instruction_len = u16_at(data, pos)? as usize;
+        pos += 2;
+        if !in_bounds(pos, instruction_len) {
+            return Err(TtfError::Malformed);
+        }
+        pos += instruction_len;
+
+        let mut flags = Vec::with_capacity(num_points);
+        while flags.len() < num_points {
+            let f = u8_at(data, pos)?;
+            pos += 1;
+            flags.push(f);
+            if f & 0x08 != 0 {
+                let repeat = u8_at(data, pos)?;
+                pos += 1;
+                for _ in 0..repeat {
+                    flags.push(f);
+                }
+            }
+        }
+
+        let mut xs = Vec::with_capacity(num_points);
+        let mut x = 0i32;
+        for &f in &flags {
+            if f & 0x02 != 0 {
+                let dx = u8_at(data, pos)? as i32;
+                pos += 1;
+                x += if f & 0x10 != 0 { dx } else { -dx };
+            } else if f & 0x10 == 0 {
+                x += i16_at(data, pos)? as i32;
+                pos += 2;
+            }
+            xs.push(x);
+        }
+
+        let mut ys = Vec::with_capacity(num_points);
+        let mut y = 0i32;
+        for &f in &flags {
+            if f & 0x04 != 0 {
+                let dy = u8_at(data, pos)? as i32;
+                pos += 1;
+                y += if f & 0x20 != 0 { dy } else { -dy };
+            } else if f & 0x20 == 0 {
+                y += i16_at(data, pos)? as i32;
+                pos += 2;
+            }
+            ys.push(y);
+        }
+
+        let mut contours = Vec::with_capacity(num_contours);
+        let mut point_start = 0;
+        for &point_end in &end_pts {
+            // `end_pts` is supposed to be non-decreasing with every entry
+            // under `num_points` -- a malformed glyph can violate that, which
+            // would otherwise panic slicing `flags`/`xs`/`ys` below
+            if point_end < point_start || point_end >= num_points {
+                return Err(TtfError::Malformed);
+            }
+            let on_curve: Vec<bool> = flags[point_start..=point_end].iter().map(|f| f & 0x01 != 0).collect();
+            let pts: Vec<(f32, f32)> = (point_start..=point_end)
+                .map(|i| (xs[i] as f32 * scale, ys[i] as f32 * scale))
+                .collect();
+            contours.push(flatten_contour(&pts, &on_curve));
+            point_start = point_end + 1;
+        }
+
+        Ok(Outline { contours, advance_px, x_min_px: x_min, x_max_px: x_max, y_min_px: y_min, y_max_px: y_max })
+    }
+}
+
+/// TrueType contours alternate on/off-curve points, with an off-curve point
+/// the control point of a quadratic Bezier between its neighbors (and an
+/// implied on-curve point inserted at the midpoint of two consecutive
+/// off-curve points). This walks that convention and subdivides each curve
+/// into line segments, producing a plain closed polygon.
+fn flatten_contour(pts: &[(f32, f32)], on_curve: &[bool]) -> Vec<(f32, f32)> {
+    let n = pts.len();
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let mut polygon = Vec::new();
+    let point_at = |i: usize| pts[i % n];
+    let on_curve_at = |i: usize| on_curve[i % n];
+    let midpoint = |a: (f32, f32), b: (f32, f32)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+
+    // start on an on-curve point (or a synthesized midpoint if none exists)
+    let start_idx = (0..n).find(|&i| on_curve_at(i));
+    let mut cur = match start_idx {
+        Some(i) => point_at(i),
+        None => midpoint(point_at(0), point_at(1)),
+    };
+    polygon.push(cur);
+
+    let first = start_idx.unwrap_or(0);
+    let mut i = first;
+    for _ in 0..n {
+        let next_i = i + 1;
+        if on_curve_at(next_i) {
+            cur = point_at(next_i);
+            polygon.push(cur);
+            i = next_i;
+        } else {
+            let control = point_at(next_i);
+            let end = if on_curve_at(next_i + 1) { point_at(next_i + 1) } else { midpoint(control, point_at(next_i + 1)) };
+            const STEPS: usize = 8;
+            for step in 1..=STEPS {
+                let t = step as f32 / STEPS as f32;
+                let mt = 1.0 - t;
+                let x = mt * mt * cur.0 + 2.0 * mt * t * control.0 + t * t * end.0;
+                let y = mt * mt * cur.1 + 2.0 * mt * t * control.1 + t * t * end.1;
+                polygon.push((x, y));
+            }
+            cur = end;
+            i = next_i;
+        }
+    }
+
+    polygon
+}
+
+/// rasterizes an outline's contours into a `width` x `height` coverage
+/// buffer (0 = empty, 255 = fully covered), `origin_px` being the pixel
+/// position of the buffer's row-0/column-0 corner in glyph space (its
+/// minimum-x, minimum-y corner; glyph space has y pointing up). Coverage is
+/// a 4x4 supersample average per pixel (even-odd fill rule across contours)
+/// rather than true analytic coverage, which is plenty for a 1-bit panel
+/// that's about to threshold it right back down anyway.
+pub fn rasterize(outline: &Outline, origin_px: (f32, f32), width: usize, height: usize) -> Vec<u8> {
+    const SUPER: usize = 4;
+    let mut coverage = Vec::with_capacity(width * height);
+    coverage.resize(width * height, 0u8);
+
+    for py in 0..height {
+        for px in 0..width {
+            let mut hits = 0u32;
+            for sy in 0..SUPER {
+                for sx in 0..SUPER {
+                    let x = origin_px.0 + px as f32 + (sx as f32 + 0.5) / SUPER as f32;
+                    let y = origin_px.1 + py as f32 + (sy as f32 + 0.5) / SUPER as f32;
+                    if point_in_contours(outline, x, y) {
+                        hits += 1;
+                    }
+                }
+            }
+            coverage[py * width + px] = ((hits * 255) / (SUPER * SUPER) as u32) as u8;
+        }
+    }
+    coverage
+}
+
+/// even-odd fill rule: count crossings of a horizontal ray cast from `(x,y)`
+fn point_in_contours(outline: &Outline, x: f32, y: f32) -> bool {
+    let mut inside = false;
+    for contour in &outline.contours {
+        let n = contour.len();
+        if n < 2 {
+            continue;
+        }
+        for i in 0..n {
+            let (x0, y0) = contour[i];
+            let (x1, y1) = contour[(i + 1) % n];
+            if (y0 > y) != (y1 > y) {
+                let x_cross = x0 + (y - y0) * (x1 - x0) / (y1 - y0);
+                if x_cross > x {
+                    inside = !inside;
+                }
+            }
+        }
+    }
+    inside
+}
+
+/// lays out `text` left-to-right starting at `origin` (baseline, not top-left
+/// -- `y` increases downward so glyphs are drawn above `origin.y` by their
+/// ascent), thresholding each glyph's coverage to `BinaryColor` at >= 50%.
+/// Returns the total advance in pixels, so a caller can keep a running
+/// `cur_line`/cursor instead of a per-font magic constant.
+pub fn draw_text<D: DrawTarget<BinaryColor>>(display: &mut D, font: &Font, size_px: f32, origin: Point, text: &str) -> f32 {
+    let mut cursor_x = origin.x as f32;
+
+    for ch in text.chars() {
+        let glyph_id = font.glyph_id(ch).unwrap_or(0);
+        // a malformed glyph degrades to a zero-advance no-op rather than
+        // aborting the whole string, same as the composite-glyph fallback
+        let outline = font.outline(glyph_id, size_px).unwrap_or(Outline {
+            contours: Vec::new(),
+            advance_px: 0.0,
+            x_min_px: 0.0,
+            x_max_px: 0.0,
+            y_min_px: 0.0,
+            y_max_px: 0.0,
+        });
+
+        if !outline.contours.is_empty() {
+            let width = (outline.x_max_px - outline.x_min_px).ceil().max(1.0) as usize;
+            let height = (outline.y_max_px - outline.y_min_px).ceil().max(1.0) as usize;
+            let origin_px = (outline.x_min_px, outline.y_min_px);
+            let coverage = rasterize(&outline, origin_px, width, height);
+
+            // row 0 of the coverage buffer is the glyph's lowest scanline
+            // (font y is up, screen y is down), so it maps to the screen row
+            // nearest the baseline rather than the topmost one
+            for row in 0..height {
+                for col in 0..width {
+                    if coverage[row * width + col] >= 128 {
+                        let coord = Point::new(
+                            cursor_x as i32 + outline.x_min_px as i32 + col as i32,
+                            origin.y - (outline.y_min_px as i32 + row as i32),
+                        );
+                        display.draw_pixel(Pixel(coord, BinaryColor::On));
+                    }
+                }
+            }
+        }
+
+        cursor_x += outline.advance_px;
+    }
+
+    cursor_x - origin.x as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// builds a `Font` directly from hand-laid-out `hmtx`/`glyf`/`loca` bytes
+    /// rather than a full sfnt file -- `outline()` only ever touches those
+    /// three tables plus `units_per_em`/`num_glyphs`/`num_h_metrics`, so this
+    /// is the minimum needed to drive it without a real table directory
+    fn font_from_raw<'a>(data: &'a [u8], glyf_offset: u32, loca_offset: u32) -> Font<'a> {
+        Font {
+            data,
+            units_per_em: 1000,
+            ascender: 0,
+            descender: 0,
+            num_glyphs: 1,
+            num_h_metrics: 1,
+            loca_long: false,
+            cmap_offset: 0,
+            loca_offset,
+            glyf_offset,
+            hmtx_offset: 0,
+        }
+    }
+
+    #[test]
+    fn outline_rejects_decreasing_end_pts() {
+        #[rustfmt::skip]
+        let data: Vec<u8> = vec![
+            0x00, 0x0A, 0x00, 0x00, // hmtx[0]: advance_width=10, lsb=0
+            0x00, 0x02,             // glyf: num_contours=2
+            0x00, 0x00, 0x00, 0x00, // x_min=0, y_min=0
+            0x00, 0x00, 0x00, 0x00, // x_max=0, y_max=0
+            0x00, 0x05,             // end_pts[0] = 5 -- claims 6 points exist
+            0x00, 0x03,             // end_pts[1] = 3 -- but this is *less* than end_pts[0]
+            0x00, 0x00,             // instruction_len = 0
+            0x01, 0x01, 0x01, 0x01, // flags: 4 on-curve points (num_points = 3 + 1 = 4)
+            0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, // x deltas
+            0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, // y deltas
+            0x00, 0x00, 0x00, 0x12, // loca[0] = 0, loca[1] = 18 (-> byte offset 36)
+        ];
+        let font = font_from_raw(&data, 4, 40);
+
+        // end_pts[0]=5 is already >= num_points(4), so this must fail the
+        // ordering/bounds check instead of slicing `flags`/`xs`/`ys` out of range
+        assert_eq!(font.outline(0, 16.0), Err(TtfError::Malformed));
+    }
+
+    #[test]
+    fn outline_rejects_truncated_instruction_run() {
+        #[rustfmt::skip]
+        let data: Vec<u8> = vec![
+            0x00, 0x0A, 0x00, 0x00, // hmtx[0]: advance_width=10, lsb=0
+            0x00, 0x01,             // glyf: num_contours=1
+            0x00, 0x00, 0x00, 0x00, // x_min=0, y_min=0
+            0x00, 0x00, 0x00, 0x00, // x_max=0, y_max=0
+            0x00, 0x00,             // end_pts[0] = 0
+            0xFF, 0xFF,             // instruction_len = 65535, far past this glyph's own range
+            0x00, 0x00, 0x00, 0x07, // loca[0] = 0, loca[1] = 7 (-> byte offset 14)
+        ];
+        let font = font_from_raw(&data, 4, 18);
+
+        assert_eq!(font.outline(0, 16.0), Err(TtfError::Malformed));
+    }
+}
@@ -0,0 +1,119 @@
+//! Minimal single-threaded, poll-based async executor for `main`'s event
+//! loop. Nothing here drives a real wakeup -- every spawned task is polled
+//! once per [`Executor::run_once`] round regardless of its waker, so a task
+//! that isn't ready just returns `Poll::Pending` and gets polled again next
+//! round. [`Delay`] is the timer queue: it checks the existing millisecond
+//! tick (`hal_time::get_time_ms`) instead of a task looping on a manual
+//! `get_time_ms` delta itself.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use betrusted_hal::hal_time::get_time_ms;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+fn noop_clone(_: *const ()) -> RawWaker {
+    raw_waker()
+}
+fn noop(_: *const ()) {}
+static VTABLE: RawWakerVTable = RawWakerVTable::new(noop_clone, noop, noop, noop);
+fn raw_waker() -> RawWaker {
+    RawWaker::new(core::ptr::null(), &VTABLE)
+}
+
+/// every task is polled unconditionally every round, so there's nothing for
+/// a real waker to do -- this just satisfies `Future::poll`'s signature
+fn noop_waker() -> Waker {
+    unsafe { Waker::from_raw(raw_waker()) }
+}
+
+/// a spawned task, boxed so tasks of different concrete `async fn` types can
+/// share one `Vec`. Borrows from `main`'s stack frame (display, shared
+/// state, ...) rather than owning everything, so no `'static` bound.
+type Task<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+/// round-robins every spawned task, dropping one if it ever completes --
+/// none of `main`'s tasks are expected to, since each is its own `loop { ... }`
+pub struct Executor<'a> {
+    tasks: Vec<Task<'a>>,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new() -> Self {
+        Executor { tasks: Vec::new() }
+    }
+
+    pub fn spawn(&mut self, task: impl Future<Output = ()> + 'a) {
+        self.tasks.push(Box::pin(task));
+    }
+
+    /// poll every still-running task once
+    pub fn run_once(&mut self) {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut i = 0;
+        while i < self.tasks.len() {
+            match self.tasks[i].as_mut().poll(&mut cx) {
+                Poll::Pending => i += 1,
+                Poll::Ready(()) => {
+                    self.tasks.remove(i);
+                }
+            }
+        }
+    }
+}
+
+/// resolves once `duration_ms` has elapsed, checked against
+/// `hal_time::get_time_ms` -- the timer queue the periodic tasks in `main`
+/// are built on
+pub struct Delay {
+    target_ms: u32,
+}
+
+impl Delay {
+    pub fn new_ms(duration_ms: u32) -> Self {
+        let p = unsafe { betrusted_pac::Peripherals::steal() };
+        Delay { target_ms: get_time_ms(&p).wrapping_add(duration_ms) }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<()> {
+        let p = unsafe { betrusted_pac::Peripherals::steal() };
+        if (get_time_ms(&p).wrapping_sub(self.target_ms) as i32) >= 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// resolves on the *next* poll -- lets a task hand control back to the
+/// executor between iterations of its own internal loop without waiting on
+/// a timer
+pub struct YieldNow {
+    polled: bool,
+}
+
+impl YieldNow {
+    pub fn new() -> Self {
+        YieldNow { polled: false }
+    }
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<()> {
+        if self.polled {
+            Poll::Ready(())
+        } else {
+            self.get_mut().polled = true;
+            Poll::Pending
+        }
+    }
+}
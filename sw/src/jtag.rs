@@ -42,6 +42,23 @@ pub enum JtagEndian {
     Little   // LSB-first shiftout
 }
 
+/// `dtmcs`'s IR opcode and register width are fixed by the RISC-V debug
+/// spec itself, unlike `dmi`'s opcode which each core's TAP assigns on its
+/// own (hence `DtmJtag::new` taking it as a parameter)
+const DTMCS_IR: u32 = 0x10;
+const DTM_IR_WIDTH: usize = 5;
+/// bit 16 of `dtmcs`: writing a 1 clears a sticky DMI busy/error state
+const DTMCS_DMIRESET: u32 = 1 << 16;
+
+/// op codes carried in a `dmi` DR's low 2 bits
+const DMI_OP_NOP: u32 = 0;
+const DMI_OP_READ: u32 = 1;
+const DMI_OP_WRITE: u32 = 2;
+/// reply-only: the previous operation hadn't finished yet (a "sticky"
+/// error -- every future shift keeps reporting it until `dtmcs.dmireset`
+/// clears it, not just the one that caused it)
+const DMI_OP_BUSY: u32 = 3;
+
 /// option 1: make a "leg" machine that contains the shift-in/shift-out records specific to each leg
 /// option 2: make a comprehensive machine that receives meta-commands to transition between states
 /// 
@@ -59,6 +76,10 @@ pub struct JtagLeg {
     i: Vec<bool>,
     /// a tag for the leg, to be used by higher level logic to track pending/done entries
     tag: String,
+    /// if set, `Shift` only clocks this many bits per visit to `Pause`
+    /// instead of running the whole leg to completion in one go -- see
+    /// `set_pause_chunk`
+    pause_chunk: Option<usize>,
 }
 
 /*
@@ -89,9 +110,22 @@ impl JtagLeg {
             o: Vec::new(),
             i: Vec::new(),
             tag: String::from(mytag),
+            pause_chunk: None,
         }
     }
 
+    /// opts this leg into pausing mid-`Shift` every `chunk` bits instead of
+    /// running to completion in one `next()`/`step()` sequence -- the TAP
+    /// parks in Pause-DR/Pause-IR between chunks (see
+    /// `JtagMach::resume`/`JtagMach::is_paused`) so a cooperative scheduler
+    /// can interleave a multi-kilobit scan with other work. A leg without
+    /// this set (the default) shifts to completion as before. Legs driven
+    /// this way should be stepped via `JtagMach::step`, not `next` --
+    /// `next` blocks until the leg completes and won't resume a paused one.
+    pub fn set_pause_chunk(&mut self, chunk: usize) {
+        self.pause_chunk = Some(chunk);
+    }
+
     /// `push` will take data in the form of an unsigned int (either u128 or u32)
     /// and append it to the JTAG input vector in preparation for sending. 
     /// "count" specifies the number of bits of the vector that are valid, and 
@@ -109,10 +143,10 @@ impl JtagLeg {
         for i in 0..count {
             match endian {
                 JtagEndian::Little => {
-                    if (data & (1 << i)) == 0 { self.i.push(false) } else { self.i.push(true) }
+                    if (data & (1 << i)) == 0 { self.o.push(false) } else { self.o.push(true) }
                 },
                 JtagEndian::Big => {
-                    if (data & (1 << (count-i))) == 0 { self.i.push(false) } else { self.i.push(true) }
+                    if (data & (1 << (count-1-i))) == 0 { self.o.push(false) } else { self.o.push(true) }
                 },
             }
         }
@@ -123,17 +157,17 @@ impl JtagLeg {
         for i in 0..count {
             match endian {
                 JtagEndian::Little => {
-                    if (data & (1 << i)) == 0 { self.i.push(false) } else { self.i.push(true) }
+                    if (data & (1 << i)) == 0 { self.o.push(false) } else { self.o.push(true) }
                 },
                 JtagEndian::Big => {
-                    if (data & (1 << (count-i))) == 0 { self.i.push(false) } else { self.i.push(true) }
+                    if (data & (1 << (count-1-i))) == 0 { self.o.push(false) } else { self.o.push(true) }
                 },
             }
         }
     }
 
     pub fn pop_u32(&mut self, count: usize, endian: JtagEndian) -> Option<u32> {
-        if self.o.len() < count {
+        if self.i.len() < count {
             // error out before trying to touch the vector, so that in case
             // of a parameter error we can try again without having lost our data
             // in general, "count" should be very well specified in this protocol.
@@ -145,20 +179,32 @@ impl JtagLeg {
             match endian {
                 JtagEndian::Big => {
                     data <<= 1;
-                    if self.o.pop().unwrap() { data |= 0x1; }
+                    if self.i.pop().unwrap() { data |= 0x1; }
                 }
                 JtagEndian::Little => {
                     data >>= 1;
-                    if self.o.pop().unwrap() { data |= 0x8000_0000; }
+                    if self.i.pop().unwrap() { data |= 0x8000_0000; }
                 }
             }
         }
 
+        // the `Little` loop above inserts each bit at the top of the word and
+        // shifts right as more bits arrive, since it doesn't know up front how
+        // many are coming -- so a short pop leaves its result parked in the
+        // high `count` bits instead of the low ones. Bring it back down so
+        // `pop_u32(count, ..)` returns the same value range `push_u32(data,
+        // count, ..)` was given, regardless of `count`.
+        if let JtagEndian::Little = endian {
+            if count > 0 {
+                data >>= 32 - count;
+            }
+        }
+
         Some(data)
     }
 
     pub fn pop_u128(&mut self, count: usize, endian: JtagEndian) -> Option<u128> {
-        if self.o.len() < count {
+        if self.i.len() < count {
             return None;
         }
 
@@ -167,15 +213,22 @@ impl JtagLeg {
             match endian {
                 JtagEndian::Big => {
                     data <<= 1;
-                    if self.o.pop().unwrap() { data |= 0x1; }
+                    if self.i.pop().unwrap() { data |= 0x1; }
                 },
                 JtagEndian::Little => {
                     data >>= 1;
-                    if self.o.pop().unwrap() { data |= 0x8000_0000_0000_0000_0000_0000_0000_0000; }
+                    if self.i.pop().unwrap() { data |= 0x8000_0000_0000_0000_0000_0000_0000_0000; }
                 }
             }
         }
 
+        // same high-bits-first quirk as `pop_u32`, see the comment there
+        if let JtagEndian::Little = endian {
+            if count > 0 {
+                data >>= 128 - count;
+            }
+        }
+
         Some(data)
     }
     
@@ -184,10 +237,29 @@ impl JtagLeg {
     }
 }
 
-trait JtagPhy {
+pub(crate) trait JtagPhy {
     fn new() -> Self;
-    fn sync(&mut self, tdi: bool, tms: bool) -> bool; 
+    fn sync(&mut self, tdi: bool, tms: bool) -> bool;
     fn nosync(&mut self, tdi: bool, tms: bool, tck: bool) -> bool;
+
+    /// clocks a whole run of `tms`/`tdi` pin states (same length) in one
+    /// transfer and returns the sampled TDO for every position flagged in
+    /// `capture` (also same length), in order -- for a transport like UART
+    /// where `sync`/`nosync` pay a full round trip per bit, this is what
+    /// lets a caller shift a long DR/IR without one round trip per clock.
+    /// Default implementation is the naive one-`sync`-per-bit fallback;
+    /// override it for a transport that can actually pipeline (see
+    /// `JtagUartPhy`).
+    fn sequence(&mut self, tms: &[bool], tdi: &[bool], capture: &[bool]) -> Vec<bool> {
+        let mut result = Vec::new();
+        for i in 0..tms.len() {
+            let tdo = self.sync(tdi[i], tms[i]);
+            if capture[i] {
+                result.push(tdo);
+            }
+        }
+        result
+    }
 }
 
 pub struct JtagUartPhy {
@@ -241,9 +313,109 @@ impl JtagPhy for JtagUartPhy {
             false
         }
     }
+
+    /// writes every command byte back-to-back before reading any reply --
+    /// turning `tms.len()` blocking round trips into one pipelined burst,
+    /// which is what actually cuts the latency `sequence` exists for. Reads
+    /// one reply byte per command regardless of `capture` (the wire
+    /// protocol always replies), but only keeps the sampled TDO at
+    /// positions `capture` flags.
+    fn sequence(&mut self, tms: &[bool], tdi: &[bool], capture: &[bool]) -> Vec<bool> {
+        let n = tms.len();
+        for i in 0..n {
+            let mut c: u8 = JtagUartPhy::SYNC_UART_CODE;
+            if tdi[i] { c |= JtagUartPhy::MASK_TDI; }
+            if tms[i] { c |= JtagUartPhy::MASK_TMS; }
+            self.uart.write(c);
+        }
+
+        let mut result = Vec::new();
+        for i in 0..n {
+            let tdo = self.uart.read() == 0x31;
+            if capture[i] {
+                result.push(tdo);
+            }
+        }
+        result
+    }
+}
+
+/// drives the same UART-framed pins as `JtagUartPhy` (it holds one and
+/// delegates `sync`/`nosync` to it -- same wire framing to the target, no
+/// reimplementation), but is meant to be served by
+/// [`JtagMach::serve_remote_bitbang`] instead of walked by the leg-based
+/// state machine. Also tracks the last applied pin state plus the
+/// `trst`/`srst`/status-LED lines the `remote_bitbang` protocol controls
+/// outside of `tck`/`tms`/`tdi`, none of which this board has a real signal
+/// for yet -- they're tracked here so `openocd` gets sane replies and a
+/// later wiring to a real GPIO just has to read these fields.
+pub struct JtagRemoteBitbangPhy {
+    inner: JtagUartPhy,
+    last_tck: bool,
+    last_tms: bool,
+    last_tdi: bool,
+    trst: bool,
+    srst: bool,
+    led: bool,
+}
+
+impl JtagRemoteBitbangPhy {
+    pub fn trst(&self) -> bool { self.trst }
+    pub fn srst(&self) -> bool { self.srst }
+    pub fn led(&self) -> bool { self.led }
+}
+
+impl JtagPhy for JtagRemoteBitbangPhy {
+    fn new() -> Self {
+        JtagRemoteBitbangPhy {
+            inner: JtagUartPhy::new(),
+            last_tck: false,
+            last_tms: false,
+            last_tdi: false,
+            trst: false,
+            srst: false,
+            led: false,
+        }
+    }
+
+    fn sync(&mut self, tdi: bool, tms: bool) -> bool {
+        self.inner.sync(tdi, tms)
+    }
+
+    fn nosync(&mut self, tdi: bool, tms: bool, tck: bool) -> bool {
+        self.last_tdi = tdi;
+        self.last_tms = tms;
+        self.last_tck = tck;
+        self.inner.nosync(tdi, tms, tck)
+    }
+
+    fn sequence(&mut self, tms: &[bool], tdi: &[bool], capture: &[bool]) -> Vec<bool> {
+        self.inner.sequence(tms, tdi, capture)
+    }
+}
+
+/// longest chain `scan_chain` assumes when sizing its flush legs -- a chain
+/// deeper than this only reports its first `MAX_SCAN_DEVICES` TAPs
+const MAX_SCAN_DEVICES: usize = 8;
+
+/// result of `JtagMach::scan_chain`: one entry per TAP discovered, in
+/// DR-chain order (so a caller can target a specific TAP by its index into
+/// `idcodes`), plus the chain's combined IR length. IR length can't be
+/// broken out per TAP without per-device documentation of where one TAP's
+/// instruction register ends and the next begins, so it's reported as one
+/// combined total.
+pub struct ScanChainResult {
+    /// `Some(idcode)` for a TAP that reported a 32-bit IDCODE (LSB 1);
+    /// `None` for a TAP that reported BYPASS (a single 0 bit) instead
+    pub idcodes: Vec<Option<u32>>,
+    /// total IR chain length in bits, summed across every TAP
+    pub total_ir_bits: usize,
 }
 
-pub struct JtagMach {
+/// generic over its PHY (defaulting to the real `JtagUartPhy`) so a test can
+/// drive the whole leg/state-machine logic -- `scan_chain`, `DtmJtag`, etc. --
+/// against a scripted mock PHY instead of real UART-attached silicon.
+pub struct JtagMach<P: JtagPhy = JtagUartPhy> {
     /// current state (could be in one of two generics, or in DR/IR chain; check top of Vector for current chain)
     s: JtagState,
     /// a vector of legs to traverse. An entry stays in pending until the traversal is complete. Aborted
@@ -254,17 +426,21 @@ pub struct JtagMach {
     /// the current leg being processed
     current: Option<JtagLeg>,
     /// a PHY that implements the JtagPhy traits
-    phy: JtagUartPhy,
+    phy: P,
+    /// set by `resume()`, consumed by `step()`'s `Pause` arm on its next
+    /// visit to actually leave Pause-DR/Pause-IR
+    resume_requested: bool,
 }
 
-impl JtagMach {
+impl<P: JtagPhy> JtagMach<P> {
     pub fn new() -> Self {
         JtagMach {
             s: JtagState::TestReset,
             pending: Vec::new(),
             done: Vec::new(),
             current: None,
-            phy: JtagUartPhy::new(),
+            phy: P::new(),
+            resume_requested: false,
         }
     }
 
@@ -296,6 +472,22 @@ impl JtagMach {
         }
     }
 
+    /// true if the current leg is parked in Pause-DR/Pause-IR, waiting on
+    /// a `resume()` call to continue clocking its remaining bits
+    pub fn is_paused(&self) -> bool {
+        matches!(self.s, JtagState::Pause)
+    }
+
+    /// asks a leg parked in Pause (via a `pause_chunk` boundary) to
+    /// continue: the next `step()` call while still in `Pause` drives
+    /// Pause -> Exit2 -> Shift and keeps clocking the remaining bits.
+    /// No-op if nothing is currently paused.
+    pub fn resume(&mut self) {
+        if self.is_paused() {
+            self.resume_requested = true;
+        }
+    }
+
     /// step() -- move state machine by one cycle
     /// if there is nothing in the pending queue, stay in idle
     /// if something in the pending queue, traverse to execute it
@@ -346,29 +538,61 @@ impl JtagMach {
                 self.s = JtagState::Shift;
             },
             JtagState::Shift => {
-                // shift data until the input vector is exhausted
+                // drain one chunk of the remaining output vector in one
+                // batched PHY transfer instead of one phy.sync() round
+                // trip per bit -- the whole leg if it has no pause_chunk
+                // set, or up to pause_chunk bits if it does, leaving the
+                // rest in `cur.o` for a later resumed Shift to continue.
                 let mut cur: JtagLeg = self.current.as_mut().unwrap().clone();
                 if cur.o.len() > 0 {
-                    let tdi: bool = cur.o.pop().unwrap();
-                    let tdo: bool = self.phy.sync(tdi, false);
-                    cur.i.push(tdo);
-                } else {
-                    self.phy.sync(false, true);
-                    self.s = JtagState::Exit1;
+                    let chunk = cur.pause_chunk.unwrap_or(cur.o.len()).min(cur.o.len());
+                    // cur.o is drained bit-by-bit from its end (LIFO), so
+                    // the shift order is the reverse of how it's stored
+                    let tdi_seq: Vec<bool> = cur.o.iter().rev().take(chunk).cloned().collect();
+                    let remaining = cur.o.len() - chunk;
+                    cur.o.truncate(remaining);
+                    let tms_seq: Vec<bool> = vec![false; tdi_seq.len()];
+                    let capture_seq: Vec<bool> = vec![true; tdi_seq.len()];
+                    let tdo_seq = self.phy.sequence(&tms_seq, &tdi_seq, &capture_seq);
+                    for tdo in tdo_seq {
+                        cur.i.push(tdo);
+                    }
                 }
+                self.phy.sync(false, true);
+                self.s = JtagState::Exit1;
                 self.current = Some(cur);
             },
             JtagState::Exit1 => {
-                self.phy.sync(false, true);
-                self.s = JtagState::Update;
+                // a leg with bits still left in `o` only got here because
+                // its pause_chunk cut Shift short -- park in Pause-DR/
+                // Pause-IR instead of running on to Update, so the caller
+                // can yield and `resume()` later without losing the leg
+                let more_to_shift = self.current.as_ref().map_or(false, |cur| cur.o.len() > 0);
+                if more_to_shift {
+                    self.phy.sync(false, false);
+                    self.s = JtagState::Pause;
+                } else {
+                    self.phy.sync(false, true);
+                    self.s = JtagState::Update;
+                }
             },
             JtagState::Pause => {
-                self.phy.sync(false, true);
-                self.s = JtagState::Exit2;
+                if self.resume_requested {
+                    self.resume_requested = false;
+                    self.phy.sync(false, true); // Pause -> Exit2
+                    self.s = JtagState::Exit2;
+                } else {
+                    // parked: hold TMS=0, legally sitting in Pause-DR/
+                    // Pause-IR until `resume()` is called
+                    self.phy.sync(false, false);
+                }
             },
             JtagState::Exit2 => {
-                self.phy.sync(false, true);
-                self.s = JtagState::Update;
+                // only reached via an explicit resume() from Pause, and
+                // only when the leg still has bits left to shift -- go
+                // back to Shift to continue where it left off
+                self.phy.sync(false, false);
+                self.s = JtagState::Shift;
             },
             JtagState::Update => {
                 self.phy.sync(false, true);
@@ -425,4 +649,417 @@ impl JtagMach {
             },
         }
     }
+
+    /// discovers the chain with no prior knowledge of what's on it.
+    /// `reset()` first, which drives every TAP to Test-Logic-Reset, loading
+    /// each one's IDCODE (or a single `0` BYPASS bit, if it has none) into
+    /// DR; then flushes DR with all-ones and reads back one candidate word
+    /// at a time, checking each word's LSB to know how far to advance
+    /// before checking the next TAP: LSB `1` means the remaining 31 bits
+    /// complete a real IDCODE, LSB `0` means that TAP is in BYPASS (a
+    /// single flop, so only that one bit belongs to it). Stops at the
+    /// first all-ones IDCODE, which only happens once the flush has run
+    /// past the last real device.
+    ///
+    /// Separately measures the chain's combined IR length: an all-ones IR
+    /// shift puts every TAP into BYPASS (one flop each), then a flush of
+    /// zeros carrying a single marker `1` bit is shifted through and the
+    /// clocks are counted until that `1` reappears on TDO -- that count is
+    /// the total number of IR flops in the chain.
+    pub fn scan_chain(&mut self) -> ScanChainResult {
+        self.reset();
+
+        let mut dr_leg = JtagLeg::new(JtagChain::DR, "scan_chain_dr");
+        for _ in 0..(MAX_SCAN_DEVICES * 32) {
+            dr_leg.push_u32(1, 1, JtagEndian::Little); // flush with all-ones
+        }
+        self.add(dr_leg);
+        self.next();
+
+        let mut idcodes = Vec::new();
+        if let Some(mut leg) = self.get() {
+            'devices: for _ in 0..MAX_SCAN_DEVICES {
+                let lsb = match leg.pop_u32(1, JtagEndian::Little) {
+                    Some(bit) => bit,
+                    None => break 'devices,
+                };
+                if lsb == 0 {
+                    idcodes.push(None); // BYPASS: a single flop, no IDCODE
+                    continue;
+                }
+                let mut idcode: u32 = lsb; // bit 0, the LSB already checked above
+                for bit_pos in 1..32 {
+                    match leg.pop_u32(1, JtagEndian::Little) {
+                        Some(bit) => if bit != 0 { idcode |= 1 << bit_pos; },
+                        None => break 'devices,
+                    }
+                }
+                if idcode == 0xFFFF_FFFF {
+                    break; // ran past the last real device
+                }
+                idcodes.push(Some(idcode));
+            }
+        }
+
+        // BYPASS probe: put every TAP in BYPASS with an all-ones IR shift
+        let mut ir_bypass_leg = JtagLeg::new(JtagChain::IR, "scan_chain_ir_bypass");
+        for _ in 0..(MAX_SCAN_DEVICES * 32) {
+            ir_bypass_leg.push_u32(1, 1, JtagEndian::Little);
+        }
+        self.add(ir_bypass_leg);
+        self.next();
+        self.get();
+
+        // then flush IR with zeros, a marker bit, and more zeros, and count
+        // clocks until the marker reappears on TDO
+        let max_ir_bits = MAX_SCAN_DEVICES * 32;
+        let mut ir_len_leg = JtagLeg::new(JtagChain::IR, "scan_chain_ir_len");
+        for _ in 0..max_ir_bits {
+            ir_len_leg.push_u32(0, 1, JtagEndian::Little);
+        }
+        ir_len_leg.push_u32(1, 1, JtagEndian::Little);
+        for _ in 0..max_ir_bits {
+            ir_len_leg.push_u32(0, 1, JtagEndian::Little);
+        }
+        self.add(ir_len_leg);
+        self.next();
+
+        let mut total_ir_bits = 0;
+        if let Some(leg) = self.get() {
+            // `i` holds TDO in shifted-out order and the marker went in right
+            // after the first `max_ir_bits` zeros, so the clocks it took to
+            // reappear are its offset past that point
+            if let Some(clocks) = leg.i.iter().skip(max_ir_bits).position(|&bit| bit) {
+                total_ir_bits = clocks;
+            }
+        }
+
+        ScanChainResult { idcodes, total_ir_bits }
+    }
+
+    /// serves OpenOCD's `remote_bitbang` protocol on `ctl` so `openocd` can
+    /// drive `phy`'s pins unmodified, instead of this crate's own SYNC/ASYNC
+    /// command bytes. `remote_bitbang` is a raw per-edge bit-bang protocol --
+    /// every command is one ASCII byte -- so it bypasses `JtagLeg` and the
+    /// pending/done queues entirely and talks straight to `phy`, the same
+    /// explicit-clock access `JtagPhy::nosync` already gives a caller.
+    /// Doesn't touch `self`'s own state machine or queues; taking `&mut self`
+    /// is just so the server lives alongside the rest of `JtagMach`'s API.
+    /// Returns once the host sends `'Q'`.
+    pub fn serve_remote_bitbang(&mut self, phy: &mut JtagRemoteBitbangPhy, ctl: &mut BtUart) {
+        loop {
+            match ctl.read() {
+                cmd @ b'0'..=b'7' => {
+                    let bits = cmd - b'0';
+                    let tck = (bits & 0b100) != 0;
+                    let tms = (bits & 0b010) != 0;
+                    let tdi = (bits & 0b001) != 0;
+                    phy.nosync(tdi, tms, tck);
+                }
+                b'R' => {
+                    let tdo = phy.nosync(phy.last_tdi, phy.last_tms, phy.last_tck);
+                    ctl.write(if tdo { b'1' } else { b'0' });
+                }
+                b'r' => { phy.trst = true; phy.srst = true; }
+                b's' => { phy.trst = true; phy.srst = false; }
+                b't' => { phy.trst = false; phy.srst = true; }
+                b'u' => { phy.trst = false; phy.srst = false; }
+                b'B' => { phy.led = true; }
+                b'b' => { phy.led = false; }
+                b'Q' => break,
+                _ => {} // unrecognized command byte -- ignore and keep serving
+            }
+        }
+    }
+}
+
+/// RISC-V Debug Transport Module (DMI) over JTAG, built entirely on
+/// `JtagMach`'s `JtagLeg`/`push_*`/`pop_*` primitives -- the register
+/// access a Microwatt-style DMI bridge (or any RISC-V debug module)
+/// expects, instead of hand-rolling the `dmi` DR's bit layout at every
+/// call site. Holds only the per-target configuration; callers still own
+/// and pass in the `JtagMach` driving the physical pins, the same way
+/// `efuse_api` takes `jtag`/`jtagphy` as arguments rather than owning them.
+pub struct DtmJtag {
+    /// IR opcode that selects the `dmi` register on this target's TAP
+    dmi_ir: u32,
+    /// width of the DMI address field, in bits
+    abits: usize,
+    /// retries on a busy/sticky-error reply before a transaction gives up
+    retries: usize,
+    /// run-test/idle cycles inserted before each status-polling shift
+    idle_cycles: usize,
+}
+
+impl DtmJtag {
+    pub fn new(dmi_ir: u32, abits: usize) -> Self {
+        DtmJtag {
+            dmi_ir,
+            abits,
+            retries: 8,
+            idle_cycles: 8,
+        }
+    }
+
+    /// overrides the default retry count and idle-cycle count used to
+    /// recover from a busy/sticky-error reply
+    pub fn set_retry(&mut self, retries: usize, idle_cycles: usize) {
+        self.retries = retries;
+        self.idle_cycles = idle_cycles;
+    }
+
+    pub fn read<P: JtagPhy>(&mut self, jtag: &mut JtagMach<P>, addr: u32) -> Option<u32> {
+        self.transact(jtag, DMI_OP_READ, addr, 0)
+    }
+
+    pub fn write<P: JtagPhy>(&mut self, jtag: &mut JtagMach<P>, addr: u32, data: u32) -> bool {
+        self.transact(jtag, DMI_OP_WRITE, addr, data).is_some()
+    }
+
+    /// issues one DMI operation and then retrieves its result, handling
+    /// the RISC-V debug spec's pipelined semantics (a `dmi` shift returns
+    /// the *previous* shift's result, so the operation's own outcome needs
+    /// a follow-up shift to collect) and the sticky-busy retry protocol.
+    /// Returns `None` if the target is still busy after `retries` attempts.
+    fn transact<P: JtagPhy>(&mut self, jtag: &mut JtagMach<P>, op: u32, addr: u32, data: u32) -> Option<u32> {
+        self.shift_dmi(jtag, op, addr, data);
+
+        for _ in 0..self.retries {
+            for _ in 0..self.idle_cycles {
+                jtag.step();
+            }
+
+            let (op_out, data_out, _addr_out) = self.shift_dmi(jtag, DMI_OP_NOP, 0, 0);
+            match op_out {
+                DMI_OP_BUSY => {
+                    self.clear_sticky_error(jtag);
+                    self.shift_dmi(jtag, op, addr, data); // retry the original op
+                }
+                _ => return Some(data_out),
+            }
+        }
+        None
+    }
+
+    /// loads the `dmi` instruction into IR, then shifts `op`/`address`/`data`
+    /// into its DR in the `op[1:0] | data[33:2] | address[abits+33:34]`
+    /// layout. The DR's reply carries whatever the *previous* shift left
+    /// behind, in the same layout -- returned as `(op, data, address)`.
+    fn shift_dmi<P: JtagPhy>(&mut self, jtag: &mut JtagMach<P>, op: u32, address: u32, data: u32) -> (u32, u32, u32) {
+        let mut ir_leg = JtagLeg::new(JtagChain::IR, "dtm_dmi_ir");
+        ir_leg.push_u32(self.dmi_ir, DTM_IR_WIDTH, JtagEndian::Little);
+        jtag.add(ir_leg);
+        jtag.next();
+        jtag.get();
+
+        let mut dr_leg = JtagLeg::new(JtagChain::DR, "dtm_dmi_dr");
+        dr_leg.push_u32(op & 0x3, 2, JtagEndian::Little);
+        // `push_u32` only accepts counts under 32, so the 32-bit data field
+        // is split into two halves rather than pushed whole (and popped back
+        // the same way)
+        dr_leg.push_u32(data & 0xFFFF, 16, JtagEndian::Little);
+        dr_leg.push_u32((data >> 16) & 0xFFFF, 16, JtagEndian::Little);
+        dr_leg.push_u32(address, self.abits, JtagEndian::Little);
+        jtag.add(dr_leg);
+        jtag.next();
+
+        if let Some(mut leg) = jtag.get() {
+            let op_out = leg.pop_u32(2, JtagEndian::Little).unwrap_or(DMI_OP_BUSY);
+            let data_lo = leg.pop_u32(16, JtagEndian::Little).unwrap_or(0);
+            let data_hi = leg.pop_u32(16, JtagEndian::Little).unwrap_or(0);
+            let addr_out = leg.pop_u32(self.abits, JtagEndian::Little).unwrap_or(0);
+            (op_out, data_lo | (data_hi << 16), addr_out)
+        } else {
+            (DMI_OP_BUSY, 0, 0)
+        }
+    }
+
+    /// writes `dtmcs.dmireset` through its own IR/DR legs to clear a
+    /// sticky DMI busy error
+    fn clear_sticky_error<P: JtagPhy>(&mut self, jtag: &mut JtagMach<P>) {
+        let mut ir_leg = JtagLeg::new(JtagChain::IR, "dtm_dtmcs_ir");
+        ir_leg.push_u32(DTMCS_IR, DTM_IR_WIDTH, JtagEndian::Little);
+        jtag.add(ir_leg);
+        jtag.next();
+        jtag.get();
+
+        let mut dr_leg = JtagLeg::new(JtagChain::DR, "dtm_dtmcs_dr");
+        dr_leg.push_u32(DTMCS_DMIRESET & 0xFFFF, 16, JtagEndian::Little);
+        dr_leg.push_u32((DTMCS_DMIRESET >> 16) & 0xFFFF, 16, JtagEndian::Little);
+        jtag.add(dr_leg);
+        jtag.next();
+        jtag.get();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+
+    /// a PHY that never talks to real pins -- each call to `sequence()` is
+    /// numbered, and a test scripts a canned TDO reply for whichever calls it
+    /// cares about; any unscripted call just gets back all-zero TDO. `sync`/
+    /// `nosync` are never inspected by `JtagMach::step`'s control flow (only
+    /// `sequence()`'s bulk result feeds into a leg's `i`), so they're no-ops.
+    struct MockPhy {
+        call_count: usize,
+        scripted: BTreeMap<usize, Vec<bool>>,
+    }
+
+    impl MockPhy {
+        fn script(&mut self, call_index: usize, reply: Vec<bool>) {
+            self.scripted.insert(call_index, reply);
+        }
+    }
+
+    impl JtagPhy for MockPhy {
+        fn new() -> Self {
+            MockPhy { call_count: 0, scripted: BTreeMap::new() }
+        }
+
+        fn sync(&mut self, _tdi: bool, _tms: bool) -> bool {
+            false
+        }
+
+        fn nosync(&mut self, _tdi: bool, _tms: bool, _tck: bool) -> bool {
+            false
+        }
+
+        fn sequence(&mut self, tms: &[bool], _tdi: &[bool], capture: &[bool]) -> Vec<bool> {
+            let reply = self.scripted.remove(&self.call_count).unwrap_or_else(|| vec![false; tms.len()]);
+            self.call_count += 1;
+            reply.into_iter().zip(capture.iter()).filter(|(_, &c)| c).map(|(bit, _)| bit).collect()
+        }
+    }
+
+    /// builds the TDO reply `sequence()` would need to hand back so that popping
+    /// `fields` (in the same order `shift_dmi` pushes them) off the DR leg decodes
+    /// back to the given values. `JtagMach::step`'s `Shift` state drains a leg's
+    /// `o` from the end (reversing push order) and appends each captured bit to
+    /// `i` in that same shifted-out order, so the reply a mock `sequence()` hands
+    /// back is the *reverse* of the leg's `o` -- build `o` the normal way on a
+    /// scratch leg, then flip it once, instead of trying to hand-reverse each
+    /// field separately.
+    fn dr_reply_for(fields: &[(u32, usize)]) -> Vec<bool> {
+        let mut scratch = JtagLeg::new(JtagChain::DR, "scratch");
+        for (data, count) in fields {
+            scratch.push_u32(*data, *count, JtagEndian::Little);
+        }
+        scratch.o.into_iter().rev().collect()
+    }
+
+    #[test]
+    fn scan_chain_finds_one_device() {
+        let mut jtag: JtagMach<MockPhy> = JtagMach::new();
+
+        const IDCODE: u32 = 0x1234_5679; // real IDCODEs always have bit 0 set
+        let mut popped_order = Vec::new();
+        for bit in 0..32 {
+            popped_order.push((IDCODE >> bit) & 1 != 0);
+        }
+        for _ in 0..32 {
+            popped_order.push(true); // terminal all-ones IDCODE that ends the scan
+        }
+        // pop_u32 drains the leg's `i` from the end, so the reply has to be padded
+        // at the front and carry the popped order reversed at the tail
+        let mut dr_reply = vec![false; MAX_SCAN_DEVICES * 32 - popped_order.len()];
+        popped_order.reverse();
+        dr_reply.extend(popped_order);
+
+        jtag.phy.script(0, dr_reply);
+
+        let result = jtag.scan_chain();
+        assert_eq!(result.idcodes, vec![Some(IDCODE)]);
+    }
+
+    #[test]
+    fn dtm_read_succeeds_on_first_try() {
+        let mut jtag: JtagMach<MockPhy> = JtagMach::new();
+        let mut dtm = DtmJtag::new(0x11, 7);
+
+        let expected_data: u32 = 0xDEAD_BEEF;
+        let expected_addr: u32 = 0x42 & 0x7F;
+        let reply = dr_reply_for(&[
+            (DMI_OP_NOP, 2),
+            (expected_data & 0xFFFF, 16),
+            ((expected_data >> 16) & 0xFFFF, 16),
+            (expected_addr, 7),
+        ]);
+        // call #0/#1 are the op's own IR/DR shift (ignored); call #3 is the
+        // DR half of the very first status-polling shift
+        jtag.phy.script(3, reply);
+
+        assert_eq!(dtm.read(&mut jtag, 0x10), Some(expected_data));
+    }
+
+    #[test]
+    fn dtm_read_retries_through_busy() {
+        let mut jtag: JtagMach<MockPhy> = JtagMach::new();
+        let mut dtm = DtmJtag::new(0x11, 7);
+
+        let busy_reply = dr_reply_for(&[(DMI_OP_BUSY, 2), (0, 16), (0, 16), (0, 7)]);
+        // call #3 is the first status poll's DR half -- make it report busy
+        jtag.phy.script(3, busy_reply);
+
+        let expected_data: u32 = 0x0BAD_CAFE;
+        let ok_reply = dr_reply_for(&[
+            (DMI_OP_NOP, 2),
+            (expected_data & 0xFFFF, 16),
+            ((expected_data >> 16) & 0xFFFF, 16),
+            (0, 7),
+        ]);
+        // calls #4/#5 are clear_sticky_error's IR/DR shift, #6/#7 are the
+        // retried original op, #8/#9 are the second status poll -- make
+        // its DR half report success
+        jtag.phy.script(9, ok_reply);
+
+        assert_eq!(dtm.read(&mut jtag, 0x10), Some(expected_data));
+    }
+
+    #[test]
+    fn resumable_dr_scan_completes_after_resume() {
+        let mut jtag: JtagMach<MockPhy> = JtagMach::new();
+
+        let mut leg = JtagLeg::new(JtagChain::DR, "resumable");
+        leg.push_u32(0xA5, 8, JtagEndian::Little); // 1010_0101
+        leg.set_pause_chunk(4);
+        jtag.add(leg);
+
+        // Shift drains `o` from its end, so the first chunk clocks out the
+        // top nibble (pushed last) and the second, post-resume chunk
+        // clocks out the bottom nibble (pushed first)
+        let first_chunk_reply = vec![true, false, true, false];
+        let second_chunk_reply = vec![false, true, false, true];
+        jtag.phy.script(0, first_chunk_reply.clone());
+        jtag.phy.script(1, second_chunk_reply.clone());
+
+        // drive step() by hand instead of next(), which blocks until a leg
+        // completes and would never give resume() a chance to run
+        jtag.step(); // TestReset -> RunIdle
+        jtag.step(); // RunIdle: pop the pending leg into `current`
+        jtag.step(); // RunIdle -> Select
+        jtag.step(); // Select -> Capture
+        jtag.step(); // Capture -> Shift
+        jtag.step(); // Shift: clocks the first chunk, 4 bits left -> Exit1
+        jtag.step(); // Exit1: bits remain -> Pause
+        assert!(jtag.is_paused());
+
+        // parked: repeated step()s without a resume() don't move on
+        jtag.step();
+        assert!(jtag.is_paused());
+
+        jtag.resume();
+        jtag.step(); // Pause -> Exit2
+        jtag.step(); // Exit2 -> Shift
+        jtag.step(); // Shift: clocks the remaining 4 bits -> Exit1
+        jtag.step(); // Exit1: nothing left -> Update
+        jtag.step(); // Update -> RunIdle, leg moves to `done`
+
+        assert!(!jtag.is_paused());
+        let done = jtag.get().expect("leg should have completed after resume");
+        let mut expected_i = first_chunk_reply;
+        expected_i.extend(second_chunk_reply);
+        assert_eq!(done.i, expected_i);
+    }
 }
\ No newline at end of file
@@ -59,6 +59,12 @@ use betrusted_hal::hal_lcd::*;
 use betrusted_hal::hal_com::*;
 use betrusted_hal::hal_kbd::*;
 use betrusted_hal::hal_xadc::*;
+use betrusted_hal::config_store::{Config, RamConfigFlash};
+use betrusted_hal::hal_lcd::hal_lcd::BetrustedDisplay;
+use betrusted_hal::qoi::draw_qoi;
+use betrusted_hal::plot::TimeSeriesPlot;
+use betrusted_hal::cursor::{draw_cursor, CursorConfig, CursorStyle};
+use betrusted_hal::text_layout::{string_width, truncate_with_ellipsis, wrap};
 use embedded_graphics::prelude::*;
 use embedded_graphics::egcircle;
 use embedded_graphics::pixelcolor::BinaryColor;
@@ -67,8 +73,14 @@ use embedded_graphics::fonts::Font8x16;
 use embedded_graphics::geometry::Point;
 use embedded_graphics::primitives::Rectangle;
 use embedded_graphics::primitives::Line;
+use alloc::boxed::Box;
 use alloc::vec::Vec;
 use alloc::string::String;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+mod executor;
+use executor::{Delay, Executor, YieldNow};
 
 use jtag::*;
 use efuse_api::*;
@@ -133,6 +145,55 @@ impl Bounce {
     }
 }
 
+/// parses a `peek`/`poke` argument as either a `0x`-prefixed hex number or a
+/// plain decimal one
+fn parse_num(s: &str) -> Option<u32> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).ok()
+    } else {
+        s.parse::<u32>().ok()
+    }
+}
+
+/// one entry in the REPL's command dispatch table: the word that invokes it,
+/// a one-line description shown by `help`, and the handler to run. New
+/// commands register here instead of growing `parse_cmd`'s `if`/`else` chain,
+/// so `help` and tab-completion automatically stay in sync with what's
+/// actually available.
+struct CommandEntry {
+    name: &'static str,
+    help: &'static str,
+    handler: fn(&mut Repl),
+}
+
+const COMMANDS: &[CommandEntry] = &[
+    CommandEntry { name: "shutdown", help: "power off the system", handler: Repl::cmd_shutdown },
+    CommandEntry { name: "buzz", help: "buzz the haptic motor briefly", handler: Repl::cmd_buzz },
+    CommandEntry { name: "blon", help: "turn the keyboard and panel backlight on", handler: Repl::cmd_blon },
+    CommandEntry { name: "bloff", help: "turn the keyboard and panel backlight off", handler: Repl::cmd_bloff },
+    CommandEntry { name: "step", help: "single-step the JTAG state machine", handler: Repl::cmd_step },
+    CommandEntry { name: "id", help: "read the FPGA IDCODE over JTAG", handler: Repl::cmd_id },
+    CommandEntry { name: "fk", help: "print the efuse crypto key", handler: Repl::cmd_fk },
+    CommandEntry { name: "fu", help: "print the efuse user word", handler: Repl::cmd_fu },
+    CommandEntry { name: "fc", help: "print the efuse control byte", handler: Repl::cmd_fc },
+    CommandEntry { name: "test1", help: "patch and burn a test efuse key", handler: Repl::cmd_test1 },
+    CommandEntry { name: "dna", help: "read the FPGA device DNA over JTAG", handler: Repl::cmd_dna },
+    CommandEntry { name: "loop", help: "loopback test of the UART TX path", handler: Repl::cmd_loop },
+    CommandEntry { name: "xadc", help: "print raw XADC voltage/temperature readings", handler: Repl::cmd_xadc },
+    CommandEntry { name: "sense", help: "print calibrated voltage/temp/noise/audio readings", handler: Repl::cmd_sense },
+    CommandEntry { name: "cfg", help: "cfg set|get|rm|erase <key> [val] -- persistent settings", handler: Repl::do_cfg_cmd },
+    CommandEntry { name: "non", help: "turn on the analog noise generator", handler: Repl::cmd_non },
+    CommandEntry { name: "noff", help: "turn off the analog noise generator", handler: Repl::cmd_noff },
+    CommandEntry { name: "peek", help: "peek <addr> [count] -- read 32-bit word(s) from memory", handler: Repl::do_peek_cmd },
+    CommandEntry { name: "poke", help: "poke <addr> <value> -- write a 32-bit word to memory", handler: Repl::do_poke_cmd },
+    CommandEntry { name: "help", help: "list available commands", handler: Repl::cmd_help },
+    CommandEntry { name: "selftest", help: "run the built-in hardware diagnostic battery", handler: Repl::cmd_selftest },
+    CommandEntry { name: "qoi", help: "qoi <addr> <len> -- decode and blit a QOI image from RAM", handler: Repl::do_qoi_cmd },
+    CommandEntry { name: "cursor", help: "cursor block|underline|bar|blink <ms> -- configure the input caret", handler: Repl::do_cursor_cmd },
+    CommandEntry { name: "layout", help: "layout qwerty|dvorak -- swap the keyboard's letter layout", handler: Repl::do_layout_cmd },
+    CommandEntry { name: "macro", help: "macro <row> <col> <expansion> -- bind a key to type a string", handler: Repl::do_macro_cmd },
+];
+
 pub struct Repl {
     /// PAC access for commands
     p: betrusted_pac::Peripherals,
@@ -156,6 +217,32 @@ pub struct Repl {
     noise0: [u16; 300],
     noise1: [u16; 300],
     update_noise: bool,
+    /// persistent key/value settings (power state, backlight level, ...)
+    config: Config<RamConfigFlash>,
+    /// last non-empty command entered, re-run when Enter is pressed on an
+    /// empty prompt -- the same "blank line repeats" convention as a gdb-style
+    /// debugger console
+    last_command: Option<String>,
+    /// how many times `last_command` has been repeated via a blank prompt in
+    /// a row
+    repeat: u32,
+    /// input caret shape and blink rate, set via the `cursor` command
+    cursor: CursorConfig,
+    /// layout swap requested by the `layout` command, applied by
+    /// `keyboard_task` (which owns the `KeyManager`) and cleared once
+    /// picked up
+    pending_layout: Option<LayoutName>,
+    /// macro trigger/expansion bound by the `macro` command, applied by
+    /// `keyboard_task` (which owns the `KeyManager`) and cleared once
+    /// picked up
+    pending_macro: Option<((usize, usize), Vec<char>)>,
+}
+
+/// the letter layouts selectable via the `layout` command
+#[derive(Clone, Copy)]
+enum LayoutName {
+    Qwerty,
+    Dvorak,
 }
 
 const PROMPT: &str = "bt> ";
@@ -178,6 +265,12 @@ impl Repl {
                 noise0: [0; 300],
                 noise1: [0; 300],
                 update_noise: false,
+                config: Config::mount(RamConfigFlash::new()).unwrap(),
+                last_command: None,
+                repeat: 0,
+                cursor: CursorConfig::default_config(),
+                pending_layout: None,
+                pending_macro: None,
             }
         };
         r.text.add_text(&mut String::from("Awaiting input."));
@@ -192,6 +285,8 @@ impl Repl {
             if self.input.len() > PROMPT.len() {
                 self.input.pop();
             }
+        } else if c == 0x9_u8.into() { // tab: complete to the longest unambiguous command prefix
+            self.complete_cmd();
         } else if c == 0xd_u8.into() { // carriage return
             self.cmd = self.input.clone();
             self.cmd.drain(..PROMPT.len());
@@ -201,9 +296,43 @@ impl Repl {
         }
     }
 
+    /// fill in the longest prefix shared by every command name that starts
+    /// with what's been typed so far. Only fires while the line is still a
+    /// single word, since completion is for the command verb, not its args.
+    fn complete_cmd(&mut self) {
+        let partial = &self.input[PROMPT.len()..];
+        if partial.contains(' ') {
+            return;
+        }
+
+        let mut matches = COMMANDS.iter().map(|entry| entry.name).filter(|name| name.starts_with(partial));
+        let first = match matches.next() {
+            Some(name) => name,
+            None => return,
+        };
+
+        let mut common_len = first.len();
+        for name in matches {
+            common_len = common_len.min(name.bytes().zip(first.bytes()).take_while(|(a, b)| a == b).count());
+        }
+
+        if common_len > partial.len() {
+            let completion = String::from(&first[..common_len]);
+            self.input.truncate(PROMPT.len());
+            self.input.push_str(&completion);
+        }
+    }
+
     pub fn get_noise0(&self) -> [u16; 300] { self.noise0 }
     pub fn get_noise1(&self) -> [u16; 300] { self.noise1 }
     pub fn get_update_noise(&self) -> bool {self.update_noise}
+    pub fn get_cursor(&self) -> CursorConfig { self.cursor }
+    /// hands the `keyboard_task` any layout swap queued by the `layout`
+    /// command, clearing it so it's only applied once
+    pub fn take_pending_layout(&mut self) -> Option<LayoutName> { self.pending_layout.take() }
+    /// hands the `keyboard_task` any macro binding queued by the `macro`
+    /// command, clearing it so it's only applied once
+    pub fn take_pending_macro(&mut self) -> Option<((usize, usize), Vec<char>)> { self.pending_macro.take() }
     pub fn sample_noise(&mut self) {
         for i in 0..300 {
             self.xadc.wait_update();
@@ -229,152 +358,539 @@ impl Repl {
     }
 
     pub fn parse_cmd(&mut self) {
-        if self.cmd.len() == 0 {
-            return;
-        } else {
-            if self.cmd.trim() == "shutdown" {
-                self.text.add_text(&mut String::from("Shutting down system"));
-                self.power = false; // the main UI loop needs to pick this up and render the display accordingly
-            } else if self.cmd.trim() == "buzz" {
-                self.text.add_text(&mut String::from("Making a buzz"));
-                unsafe{ self.p.GPIO.drive.write(|w| w.bits(4)); }
-                unsafe{ self.p.GPIO.output.write(|w| w.bits(4)); }
-                let time: u32 = get_time_ms(&self.p);
-                while get_time_ms(&self.p) - time < 250 { }
-                unsafe{ self.p.GPIO.output.write(|w| w.bits(0)); }
-            } else if self.cmd.trim() == "blon" {
-                self.text.add_text(&mut String::from("Turning backlight on"));
-                com_txrx(&self.p, 0x6007); // turn on the keyboard backlight LEDs
-                com_txrx(&self.p, 0x681F); // turn on the backlight to full brightness (31)
-            } else if self.cmd.trim() == "bloff" {
-                self.text.add_text(&mut String::from("Turning backlight off"));
-                com_txrx(&self.p, 0x6000);
-                com_txrx(&self.p, 0x6800);
-            } else if self.cmd.trim() == "step" {
-                self.jtag.step(&mut self.jtagphy);
-            } else if self.cmd.trim() == "id" {
-                self.jtag.reset(&mut self.jtagphy);
-                let mut id_leg: JtagLeg = JtagLeg::new(JtagChain::IR, "idcode");
-                id_leg.push_u32(0b001001, 6, JtagEndian::Little);
-                self.jtag.add(id_leg);
-                self.jtag.next(&mut self.jtagphy);
-                // NOW: - check the return data on .get() before using it
-                if self.jtag.get().is_none() { // discard ID code but check that there's something
-                   self.text.add_text(&mut format!("ID instruction not in get queue!"));
-                   return;
+        if self.cmd.trim().is_empty() {
+            match self.last_command.clone() {
+                Some(last) => {
+                    self.cmd = last;
+                    self.repeat += 1;
                 }
+                None => return,
+            }
+        } else {
+            self.last_command = Some(self.cmd.clone());
+            self.repeat = 0;
+        }
 
-                let mut data_leg: JtagLeg = JtagLeg::new(JtagChain::DR, "iddata");
-                data_leg.push_u32(0, 32, JtagEndian::Little);
-                self.jtag.add(data_leg);
-                self.jtag.dbg_reset();
-                self.jtag.next(&mut self.jtagphy);
-                let d: u32 = self.jtag.dbg_get();
-                if let Some(mut iddata) = self.jtag.get() { // this contains the actual idcode data
-                    self.text.add_text(&mut format!("tag: {}, code: 0x{:08x}, d:{}", iddata.tag(), iddata.pop_u32(32, JtagEndian::Little).unwrap(), d));
-                } else {
-                    self.text.add_text(&mut format!("ID data not in get queue!"));
+        let verb = self.cmd.trim().split_whitespace().next().unwrap_or("");
+        match COMMANDS.iter().find(|entry| entry.name == verb) {
+            Some(entry) => (entry.handler)(self),
+            None => self.text.add_text(&mut format!("{}: not recognized.", self.cmd.trim())),
+        }
+    }
+
+    fn cmd_shutdown(&mut self) {
+        self.text.add_text(&mut String::from("Shutting down system"));
+        self.power = false; // the main UI loop needs to pick this up and render the display accordingly
+    }
+
+    fn cmd_buzz(&mut self) {
+        self.text.add_text(&mut String::from("Making a buzz"));
+        unsafe{ self.p.GPIO.drive.write(|w| w.bits(4)); }
+        unsafe{ self.p.GPIO.output.write(|w| w.bits(4)); }
+        let time: u32 = get_time_ms(&self.p);
+        while get_time_ms(&self.p) - time < 250 { }
+        unsafe{ self.p.GPIO.output.write(|w| w.bits(0)); }
+    }
+
+    fn cmd_blon(&mut self) {
+        self.text.add_text(&mut String::from("Turning backlight on"));
+        com_txrx(&self.p, 0x6007); // turn on the keyboard backlight LEDs
+        com_txrx(&self.p, 0x681F); // turn on the backlight to full brightness (31)
+    }
+
+    fn cmd_bloff(&mut self) {
+        self.text.add_text(&mut String::from("Turning backlight off"));
+        com_txrx(&self.p, 0x6000);
+        com_txrx(&self.p, 0x6800);
+    }
+
+    fn cmd_step(&mut self) {
+        self.jtag.step(&mut self.jtagphy);
+    }
+
+    fn cmd_id(&mut self) {
+        self.jtag.reset(&mut self.jtagphy);
+        let mut id_leg: JtagLeg = JtagLeg::new(JtagChain::IR, "idcode");
+        id_leg.push_u32(0b001001, 6, JtagEndian::Little);
+        self.jtag.add(id_leg);
+        self.jtag.next(&mut self.jtagphy);
+        // NOW: - check the return data on .get() before using it
+        if self.jtag.get().is_none() { // discard ID code but check that there's something
+           self.text.add_text(&mut format!("ID instruction not in get queue!"));
+           return;
+        }
+
+        let mut data_leg: JtagLeg = JtagLeg::new(JtagChain::DR, "iddata");
+        data_leg.push_u32(0, 32, JtagEndian::Little);
+        self.jtag.add(data_leg);
+        self.jtag.dbg_reset();
+        self.jtag.next(&mut self.jtagphy);
+        let d: u32 = self.jtag.dbg_get();
+        if let Some(mut iddata) = self.jtag.get() { // this contains the actual idcode data
+            self.text.add_text(&mut format!("tag: {}, code: 0x{:08x}, d:{}", iddata.tag(), iddata.pop_u32(32, JtagEndian::Little).unwrap(), d));
+        } else {
+            self.text.add_text(&mut format!("ID data not in get queue!"));
+        }
+    }
+
+    fn cmd_fk(&mut self) { // crypto fuse
+        self.efuse.fetch(&mut self.jtag, &mut self.jtagphy);
+        let key: [u8; 32] = self.efuse.phy_key();
+        self.text.add_text(&mut String::from("Key, in hex:"));
+        let mut line = String::from("");
+        for i in (16..32).rev() {
+            line = line + &format!("{:02x}", key[i]);
+        }
+        self.text.add_text(&mut line);
+        line = String::from("");
+        for i in (0..16).rev() {
+            line = line + &format!("{:02x}", key[i]);
+        }
+        self.text.add_text(&mut line);
+    }
+
+    fn cmd_fu(&mut self) {
+        self.efuse.fetch(&mut self.jtag, &mut self.jtagphy);
+        self.text.add_text(&mut format!("user: 0x{:08x}", self.efuse.phy_user()));
+    }
+
+    fn cmd_fc(&mut self) {
+        self.efuse.fetch(&mut self.jtag, &mut self.jtagphy);
+        self.text.add_text(&mut format!("cntl: 0x{:02x}", self.efuse.phy_cntl()));
+    }
+
+    fn cmd_test1(&mut self) {
+        self.efuse.fetch(&mut self.jtag, &mut self.jtagphy);
+        let mut key: [u8; 32] = self.efuse.phy_key();
+        key[26] = 0xA0;
+        key[25] = 0x03;
+        key[24] = 0x81;
+        self.efuse.set_key(key);
+        if self.efuse.is_valid() {
+            self.text.add_text(&mut format!("Patch is valid."));
+        } else {
+            self.text.add_text(&mut format!("Patch is not valid."));
+        }
+        self.efuse.burn(&mut self.jtag, &mut self.jtagphy);
+    }
+
+    fn cmd_dna(&mut self) {
+        self.jtag.reset(&mut self.jtagphy);
+        let mut ir_leg: JtagLeg = JtagLeg::new(JtagChain::IR, "cmd");
+        ir_leg.push_u32(0b110010, 6, JtagEndian::Little);
+        self.jtag.add(ir_leg);
+        self.jtag.next(&mut self.jtagphy);
+        if self.jtag.get().is_none() { // discard ID code but check that there's something
+           self.text.add_text(&mut format!("cmd instruction not in get queue!"));
+           return;
+        }
+
+        let mut data_leg: JtagLeg = JtagLeg::new(JtagChain::DR, "dna");
+        data_leg.push_u128(0, 64, JtagEndian::Little);
+        self.jtag.add(data_leg);
+        self.jtag.next(&mut self.jtagphy);
+        if let Some(mut data) = self.jtag.get() {
+            let dna: u128 = data.pop_u128(64, JtagEndian::Little).unwrap();
+            self.text.add_text(&mut format!("{}/0x{:16x}", data.tag(), dna));
+        } else {
+            self.text.add_text(&mut format!("dna data not in queue!"));
+        }
+    }
+
+    fn cmd_loop(&mut self) {
+        unsafe { self.p.UART.ev_pending.write(|w| w.bits(self.p.UART.ev_pending.read().bits())); }
+        unsafe { self.p.UART.ev_enable.write(|w| w.bits(3)); }
+
+        // send 0-9 as a test
+        for _ in 0..10 {
+            for i in 0..10 {
+                while self.p.UART.txfull.read().bits() != 0 {}
+                unsafe { self.p.UART.rxtx.write(|w| w.bits(0x30 + i as u32)); }
+                unsafe { self.p.UART.ev_pending.write(|w| w.bits(1)); }
+            }
+            // crlf
+            unsafe { self.p.UART.rxtx.write(|w| w.bits(0xa as u32)); }
+            unsafe { self.p.UART.rxtx.write(|w| w.bits(0xd as u32)); }
+        }
+    }
+
+    fn cmd_xadc(&mut self) {
+        let vccint: u32 = self.p.INFO.xadc_vccint0.read().bits() as u32 | ((self.p.INFO.xadc_vccint1.read().bits() as u32) << 8);
+        let vccaux: u32 = self.p.INFO.xadc_vccaux0.read().bits() as u32 | ((self.p.INFO.xadc_vccaux1.read().bits() as u32) << 8);
+        let vccbram: u32 = self.p.INFO.xadc_vccbram0.read().bits() as u32 | ((self.p.INFO.xadc_vccbram1.read().bits() as u32) << 8);
+        let temp: u32 = self.p.INFO.xadc_temperature0.read().bits() as u32 | ((self.p.INFO.xadc_temperature1.read().bits() as u32) << 8);
+
+        self.text.add_text(&mut format!("vccint: {:.3}V", (vccint as f64) / 1365.0));
+        self.text.add_text(&mut format!("vccaux: {:.3}V", (vccaux as f64) / 1365.0));
+        self.text.add_text(&mut format!("vccbram: {:.3}V", (vccbram as f64) / 1365.0));
+        self.text.add_text(&mut format!("temp: {:.2}C", ((temp as f64) * 0.12304) - 273.15));
+    }
+
+    fn cmd_sense(&mut self) {
+        self.xadc.wait_update();
+        self.text.add_text(&mut format!("int:  {:.3}V  aux: {:.3}V", (self.xadc.vccint() as f64) / 1365.0, (self.xadc.vccaux() as f64) / 1365.0));
+        self.text.add_text(&mut format!("bram: {:.3}V temp: {:.2}C",
+                                        (self.xadc.vccbram() as f64) / 1365.0,
+                                        ((self.xadc.temp() as f64) * 0.12304) - 273.15 ));
+        self.text.add_text(&mut format!("vbus: {:4}mV cc1: {:4}mV cc2: {:4}mV",
+                                        self.xadc.vbus_mv(),
+                                        self.xadc.cc1_mv(),
+                                        self.xadc.cc2_mv()  ));
+        self.text.add_text(&mut format!("noise0: {:4} noise1: {:4}", self.xadc.noise0(), self.xadc.noise1()));
+        self.text.add_text(&mut format!("audio: 0x{:04x}", self.xadc.audio_sample() ));
+    }
+
+    fn cmd_non(&mut self) {
+        unsafe{ self.p.POWER.power.write(|w| w.noisebias().bit(true).noise().bits(3).self_().bit(true).state().bits(3) ); }
+        self.update_noise = true;
+    }
+
+    fn cmd_noff(&mut self) {
+        unsafe{ self.p.POWER.power.write(|w| w.noisebias().bit(false).noise().bits(0).self_().bit(true).state().bits(3) ); }
+        self.update_noise = false;
+    }
+
+    /// lists every registered command and its one-line description
+    fn cmd_help(&mut self) {
+        for entry in COMMANDS {
+            self.text.add_text(&mut format!("{} - {}", entry.name, entry.help));
+        }
+    }
+
+    /// runs the built-in diagnostic battery, printing one pass/fail line per
+    /// check followed by a final summary count -- modeled on a factory
+    /// functional-test-ROM harness, so bring-up and field diagnosis don't
+    /// need a host attached
+    fn cmd_selftest(&mut self) {
+        let checks: [(&str, fn(&mut Repl) -> Result<(), String>); 8] = [
+            ("jtag idcode", Repl::selftest_idcode),
+            ("jtag dna", Repl::selftest_dna),
+            ("xadc vccint", Repl::selftest_vccint),
+            ("xadc vccaux", Repl::selftest_vccaux),
+            ("xadc vccbram", Repl::selftest_vccbram),
+            ("xadc temp", Repl::selftest_temp),
+            ("ec com link", Repl::selftest_ec_link),
+            ("sram config", Repl::selftest_sram_config),
+        ];
+
+        let mut passed = 0;
+        for (name, check) in checks.iter() {
+            match check(self) {
+                Ok(()) => {
+                    passed += 1;
+                    self.text.add_text(&mut format!("{}: PASS", name));
                 }
-            } else if self.cmd.trim() == "fk" { // crypto fuse
-                self.efuse.fetch(&mut self.jtag, &mut self.jtagphy);
-                let key: [u8; 32] = self.efuse.phy_key();
-                self.text.add_text(&mut String::from("Key, in hex:"));
-                let mut line = String::from("");
-                for i in (16..32).rev() {
-                    line = line + &format!("{:02x}", key[i]);
+                Err(detail) => {
+                    self.text.add_text(&mut format!("{}: FAIL ({})", name, detail));
                 }
-                self.text.add_text(&mut line);
-                line = String::from("");
-                for i in (0..16).rev() {
-                    line = line + &format!("{:02x}", key[i]);
+            }
+        }
+
+        self.text.add_text(&mut format!("selftest: {}/{} passed", passed, checks.len()));
+    }
+
+    /// reads the IDCODE the same way `id` does and checks it's a plausible
+    /// code. There's no verified reference IDCODE recorded anywhere else in
+    /// this tree to compare against, so this can only catch a dead/miswired
+    /// JTAG chain (all-0 or all-1 is what a non-responding TAP shifts back)
+    /// rather than confirm the exact die.
+    fn selftest_idcode(&mut self) -> Result<(), String> {
+        self.jtag.reset(&mut self.jtagphy);
+        let mut id_leg: JtagLeg = JtagLeg::new(JtagChain::IR, "idcode");
+        id_leg.push_u32(0b001001, 6, JtagEndian::Little);
+        self.jtag.add(id_leg);
+        self.jtag.next(&mut self.jtagphy);
+        if self.jtag.get().is_none() {
+            return Err(String::from("no IR response"));
+        }
+
+        let mut data_leg: JtagLeg = JtagLeg::new(JtagChain::DR, "iddata");
+        data_leg.push_u32(0, 32, JtagEndian::Little);
+        self.jtag.add(data_leg);
+        self.jtag.next(&mut self.jtagphy);
+        match self.jtag.get() {
+            Some(mut iddata) => {
+                let code = iddata.pop_u32(32, JtagEndian::Little).unwrap();
+                if code != 0 && code != 0xFFFF_FFFF {
+                    Ok(())
+                } else {
+                    Err(format!("implausible idcode 0x{:08x}", code))
                 }
-                self.text.add_text(&mut line);
-            } else if self.cmd.trim() == "fu" {
-                self.efuse.fetch(&mut self.jtag, &mut self.jtagphy);
-                self.text.add_text(&mut format!("user: 0x{:08x}", self.efuse.phy_user()));
-            } else if self.cmd.trim() == "fc" {
-                self.efuse.fetch(&mut self.jtag, &mut self.jtagphy);
-                self.text.add_text(&mut format!("cntl: 0x{:02x}", self.efuse.phy_cntl()));
-            }  else if self.cmd.trim() == "test1" {
-                self.efuse.fetch(&mut self.jtag, &mut self.jtagphy);
-                let mut key: [u8; 32] = self.efuse.phy_key();
-                key[26] = 0xA0;
-                key[25] = 0x03;
-                key[24] = 0x81;
-                self.efuse.set_key(key);
-                if self.efuse.is_valid() {
-                    self.text.add_text(&mut format!("Patch is valid."));
+            }
+            None => Err(String::from("no DR response")),
+        }
+    }
+
+    /// reads the device DNA the same way `dna` does and checks it's non-zero
+    fn selftest_dna(&mut self) -> Result<(), String> {
+        self.jtag.reset(&mut self.jtagphy);
+        let mut ir_leg: JtagLeg = JtagLeg::new(JtagChain::IR, "cmd");
+        ir_leg.push_u32(0b110010, 6, JtagEndian::Little);
+        self.jtag.add(ir_leg);
+        self.jtag.next(&mut self.jtagphy);
+        if self.jtag.get().is_none() {
+            return Err(String::from("no IR response"));
+        }
+
+        let mut data_leg: JtagLeg = JtagLeg::new(JtagChain::DR, "dna");
+        data_leg.push_u128(0, 64, JtagEndian::Little);
+        self.jtag.add(data_leg);
+        self.jtag.next(&mut self.jtagphy);
+        match self.jtag.get() {
+            Some(mut data) => {
+                let dna: u128 = data.pop_u128(64, JtagEndian::Little).unwrap();
+                if dna != 0 {
+                    Ok(())
                 } else {
-                    self.text.add_text(&mut format!("Patch is not valid."));
+                    Err(String::from("dna read back as 0"))
                 }
-                self.efuse.burn(&mut self.jtag, &mut self.jtagphy);
-            }  else if self.cmd.trim() == "dna" { // dna
-                self.jtag.reset(&mut self.jtagphy);
-                let mut ir_leg: JtagLeg = JtagLeg::new(JtagChain::IR, "cmd");
-                ir_leg.push_u32(0b110010, 6, JtagEndian::Little);
-                self.jtag.add(ir_leg);
-                self.jtag.next(&mut self.jtagphy);
-                if self.jtag.get().is_none() { // discard ID code but check that there's something
-                   self.text.add_text(&mut format!("cmd instruction not in get queue!"));
-                   return;
+            }
+            None => Err(String::from("no DR response")),
+        }
+    }
+
+    /// checks an XADC rail against its nominal voltage, +/-10%
+    fn check_xadc_rail(&self, raw0: u32, raw1: u32, nominal_v: f64) -> Result<(), String> {
+        let millivolts = ((raw0 | (raw1 << 8)) as f64) / 1365.0;
+        let low = nominal_v * 0.9;
+        let high = nominal_v * 1.1;
+        if millivolts >= low && millivolts <= high {
+            Ok(())
+        } else {
+            Err(format!("{:.3}V outside {:.3}-{:.3}V", millivolts, low, high))
+        }
+    }
+
+    fn selftest_vccint(&mut self) -> Result<(), String> {
+        self.check_xadc_rail(self.p.INFO.xadc_vccint0.read().bits() as u32, self.p.INFO.xadc_vccint1.read().bits() as u32, 1.0)
+    }
+
+    fn selftest_vccaux(&mut self) -> Result<(), String> {
+        self.check_xadc_rail(self.p.INFO.xadc_vccaux0.read().bits() as u32, self.p.INFO.xadc_vccaux1.read().bits() as u32, 1.8)
+    }
+
+    fn selftest_vccbram(&mut self) -> Result<(), String> {
+        self.check_xadc_rail(self.p.INFO.xadc_vccbram0.read().bits() as u32, self.p.INFO.xadc_vccbram1.read().bits() as u32, 1.0)
+    }
+
+    /// checks the die temperature is within the FPGA's operating range
+    fn selftest_temp(&mut self) -> Result<(), String> {
+        let raw: u32 = self.p.INFO.xadc_temperature0.read().bits() as u32 | ((self.p.INFO.xadc_temperature1.read().bits() as u32) << 8);
+        let temp_c = (raw as f64) * 0.12304 - 273.15;
+        if temp_c > -40.0 && temp_c < 100.0 {
+            Ok(())
+        } else {
+            Err(format!("{:.1}C out of range", temp_c))
+        }
+    }
+
+    /// there's no dedicated echo opcode on the EC COM link in this tree, so
+    /// this checks for the same thing the gas-gauge poll in `main` relies on
+    /// implicitly: that the link is live at all, rather than returning the
+    /// "nothing out there" sentinel a floating/disconnected link reads back as
+    fn selftest_ec_link(&mut self) -> Result<(), String> {
+        com_txrx(&self.p, 0x7000); // pointer reset, see main's EC poll task
+        let echo = com_txrx(&self.p, 0xDEAD);
+        if echo != 0x0000 && echo != 0xFFFF {
+            Ok(())
+        } else {
+            Err(format!("EC returned 0x{:04x}", echo))
+        }
+    }
+
+    /// checks the SRAM config status latched at boot isn't the all-zero or
+    /// all-ones pattern an unconfigured/unresponsive SRAM controller reads back as
+    fn selftest_sram_config(&mut self) -> Result<(), String> {
+        let status = self.p.SRAM_EXT.config_status0.read().bits();
+        if status != 0 && status != u32::MAX {
+            Ok(())
+        } else {
+            Err(format!("status 0x{:08x}", status))
+        }
+    }
+
+    /// `cursor block|underline|bar` sets the caret shape; `cursor blink <ms>`
+    /// sets the blink interval (`0` for steady, no-blink)
+    fn do_cursor_cmd(&mut self) {
+        let rest = self.cmd.trim()[6..].trim();
+        let mut parts = rest.splitn(2, ' ');
+        let sub = parts.next().unwrap_or("");
+        let arg = parts.next().unwrap_or("").trim();
+
+        match sub {
+            "block" => { self.cursor.style = CursorStyle::Block; self.text.add_text(&mut String::from("cursor: block")); }
+            "underline" => { self.cursor.style = CursorStyle::Underline; self.text.add_text(&mut String::from("cursor: underline")); }
+            "bar" => { self.cursor.style = CursorStyle::Bar; self.text.add_text(&mut String::from("cursor: bar")); }
+            "blink" => match arg.parse::<u32>() {
+                Ok(ms) => {
+                    self.cursor.blink_interval_ms = ms;
+                    self.text.add_text(&mut format!("cursor: blink {} ms{}", ms, if ms == 0 { " (steady)" } else { "" }));
                 }
+                Err(_) => self.text.add_text(&mut String::from("cursor blink <ms>")),
+            },
+            _ => self.text.add_text(&mut String::from("cursor block|underline|bar|blink <ms>")),
+        }
+    }
+
+    /// `layout qwerty|dvorak` swaps the keyboard's letter layout at
+    /// runtime, e.g. from a settings menu -- queued here and applied by
+    /// `keyboard_task`, which is the one holding the `KeyManager`
+    fn do_layout_cmd(&mut self) {
+        let rest = self.cmd.trim()[6..].trim();
+        match rest {
+            "qwerty" => { self.pending_layout = Some(LayoutName::Qwerty); self.text.add_text(&mut String::from("layout: qwerty")); }
+            "dvorak" => { self.pending_layout = Some(LayoutName::Dvorak); self.text.add_text(&mut String::from("layout: dvorak")); }
+            _ => self.text.add_text(&mut String::from("layout qwerty|dvorak")),
+        }
+    }
 
-                let mut data_leg: JtagLeg = JtagLeg::new(JtagChain::DR, "dna");
-                data_leg.push_u128(0, 64, JtagEndian::Little);
-                self.jtag.add(data_leg);
-                self.jtag.next(&mut self.jtagphy);
-                if let Some(mut data) = self.jtag.get() {
-                    let dna: u128 = data.pop_u128(64, JtagEndian::Little).unwrap();
-                    self.text.add_text(&mut format!("{}/0x{:16x}", data.tag(), dna));
+    /// `macro <row> <col> <expansion>` binds the key at that matrix
+    /// position to type `expansion` instead of its layout's own character
+    /// -- queued here and applied by `keyboard_task`, which is the one
+    /// holding the `KeyManager`
+    fn do_macro_cmd(&mut self) {
+        let rest = self.cmd.trim()[5..].trim();
+        let mut parts = rest.splitn(3, ' ');
+        let row = parts.next().and_then(|s| s.parse::<usize>().ok());
+        let col = parts.next().and_then(|s| s.parse::<usize>().ok());
+        let expansion = parts.next();
+
+        match (row, col, expansion) {
+            (Some(row), Some(col), Some(expansion)) if !expansion.is_empty() => {
+                self.pending_macro = Some(((row, col), expansion.chars().collect()));
+                self.text.add_text(&mut format!("macro: ({},{}) -> \"{}\"", row, col, expansion));
+            }
+            _ => self.text.add_text(&mut String::from("macro <row> <col> <expansion>")),
+        }
+    }
+
+    /// handles the `cfg set|get|rm|erase` family of REPL commands
+    fn do_cfg_cmd(&mut self) {
+        let trimmed = self.cmd.trim();
+        let rest = trimmed[3..].trim(); // strip the leading "cfg"
+        let mut parts = rest.splitn(2, ' ');
+        let sub = parts.next().unwrap_or("");
+        let arg = parts.next().unwrap_or("").trim();
+
+        match sub {
+            "set" => {
+                let mut kv = arg.splitn(2, ' ');
+                let key = kv.next().unwrap_or("");
+                let val = kv.next().unwrap_or("");
+                if key.is_empty() {
+                    self.text.add_text(&mut String::from("cfg set <key> <val>"));
                 } else {
-                    self.text.add_text(&mut format!("dna data not in queue!"));
+                    match self.config.set(key, val.as_bytes()) {
+                        Ok(()) => self.text.add_text(&mut format!("set {}", key)),
+                        Err(_) => self.text.add_text(&mut String::from("cfg set failed")),
+                    }
                 }
-            } else if self.cmd.trim() == "loop" {
-                unsafe { self.p.UART.ev_pending.write(|w| w.bits(self.p.UART.ev_pending.read().bits())); }
-                unsafe { self.p.UART.ev_enable.write(|w| w.bits(3)); }
-                
-                // send 0-9 as a test
-                for _ in 0..10 {
-                    for i in 0..10 {
-                        while self.p.UART.txfull.read().bits() != 0 {}
-                        unsafe { self.p.UART.rxtx.write(|w| w.bits(0x30 + i as u32)); }
-                        unsafe { self.p.UART.ev_pending.write(|w| w.bits(1)); }
+            }
+            "get" => {
+                if arg.is_empty() {
+                    self.text.add_text(&mut String::from("cfg get <key>"));
+                } else {
+                    match self.config.get(arg) {
+                        Some(val) => {
+                            let s = core::str::from_utf8(val).unwrap_or("<binary>");
+                            self.text.add_text(&mut format!("{}={}", arg, s));
+                        }
+                        None => self.text.add_text(&mut format!("{}: not set", arg)),
                     }
-                    // crlf
-                    unsafe { self.p.UART.rxtx.write(|w| w.bits(0xa as u32)); }
-                    unsafe { self.p.UART.rxtx.write(|w| w.bits(0xd as u32)); }
                 }
-            } else if self.cmd.trim() == "xadc" {
-                let vccint: u32 = self.p.INFO.xadc_vccint0.read().bits() as u32 | ((self.p.INFO.xadc_vccint1.read().bits() as u32) << 8);
-                let vccaux: u32 = self.p.INFO.xadc_vccaux0.read().bits() as u32 | ((self.p.INFO.xadc_vccaux1.read().bits() as u32) << 8);
-                let vccbram: u32 = self.p.INFO.xadc_vccbram0.read().bits() as u32 | ((self.p.INFO.xadc_vccbram1.read().bits() as u32) << 8);
-                let temp: u32 = self.p.INFO.xadc_temperature0.read().bits() as u32 | ((self.p.INFO.xadc_temperature1.read().bits() as u32) << 8);
-
-                self.text.add_text(&mut format!("vccint: {:.3}V", (vccint as f64) / 1365.0));
-                self.text.add_text(&mut format!("vccaux: {:.3}V", (vccaux as f64) / 1365.0));
-                self.text.add_text(&mut format!("vccbram: {:.3}V", (vccbram as f64) / 1365.0));
-                self.text.add_text(&mut format!("temp: {:.2}C", ((temp as f64) * 0.12304) - 273.15));
-            } else if self.cmd.trim() == "sense" {
-                self.xadc.wait_update();
-                self.text.add_text(&mut format!("int:  {:.3}V  aux: {:.3}V", (self.xadc.vccint() as f64) / 1365.0, (self.xadc.vccaux() as f64) / 1365.0));
-                self.text.add_text(&mut format!("bram: {:.3}V temp: {:.2}C", 
-                                                (self.xadc.vccbram() as f64) / 1365.0, 
-                                                ((self.xadc.temp() as f64) * 0.12304) - 273.15 ));
-                self.text.add_text(&mut format!("vbus: {:4}mV cc1: {:4}mV cc2: {:4}mV", 
-                                                self.xadc.vbus_mv(),
-                                                self.xadc.cc1_mv(),
-                                                self.xadc.cc2_mv()  ));
-                self.text.add_text(&mut format!("noise0: {:4} noise1: {:4}", self.xadc.noise0(), self.xadc.noise1()));
-                self.text.add_text(&mut format!("audio: 0x{:04x}", self.xadc.audio_sample() ));
-            } else if self.cmd.trim() == "non" {
-                unsafe{ self.p.POWER.power.write(|w| w.noisebias().bit(true).noise().bits(3).self_().bit(true).state().bits(3) ); }
-                self.update_noise = true;
-            } else if self.cmd.trim() == "noff" {
-                unsafe{ self.p.POWER.power.write(|w| w.noisebias().bit(false).noise().bits(0).self_().bit(true).state().bits(3) ); }
-                self.update_noise = false;
-            } else {
-                self.text.add_text(&mut format!("{}: not recognized.", self.cmd.trim()));
             }
+            "rm" => {
+                if arg.is_empty() {
+                    self.text.add_text(&mut String::from("cfg rm <key>"));
+                } else {
+                    match self.config.remove(arg) {
+                        Ok(()) => self.text.add_text(&mut format!("removed {}", arg)),
+                        Err(_) => self.text.add_text(&mut String::from("cfg rm failed")),
+                    }
+                }
+            }
+            "erase" => match self.config.erase() {
+                Ok(()) => self.text.add_text(&mut String::from("config erased")),
+                Err(_) => self.text.add_text(&mut String::from("cfg erase failed")),
+            },
+            _ => {
+                self.text.add_text(&mut String::from("cfg set|get|rm|erase"));
+            }
+        }
+    }
+
+    /// `peek <addr> [count]` -- dump `count` (default 1) 32-bit words
+    /// starting at `addr` from the memory/peripheral map, the same raw
+    /// pointer access `hal_lcd`'s framebuffer uses, just at a caller-given
+    /// address instead of a fixed one. A bad `addr` prints a usage line and a
+    /// bad `count` falls back to 1, rather than panicking -- this is a
+    /// bring-up tool, so it needs to survive a typo.
+    fn do_peek_cmd(&mut self) {
+        let rest = self.cmd.trim()[4..].trim();
+        let mut args = rest.split_whitespace();
+        let addr = match args.next().and_then(parse_num) {
+            Some(a) => a,
+            None => {
+                self.text.add_text(&mut String::from("peek <addr> [count]"));
+                return;
+            }
+        };
+        let count = args.next().and_then(parse_num).unwrap_or(1).max(1);
+
+        for i in 0..count {
+            let word_addr = addr.wrapping_add(i * 4);
+            let value = unsafe { core::ptr::read_volatile(word_addr as *const u32) };
+            self.text.add_text(&mut format!("0x{:08x}: 0x{:08x}", word_addr, value));
+        }
+    }
+
+    /// `poke <addr> <value>` -- write one 32-bit word into the memory/peripheral map
+    fn do_poke_cmd(&mut self) {
+        let rest = self.cmd.trim()[4..].trim();
+        let mut args = rest.split_whitespace();
+        let addr = args.next().and_then(parse_num);
+        let value = args.next().and_then(parse_num);
+
+        match (addr, value) {
+            (Some(addr), Some(value)) => {
+                unsafe { core::ptr::write_volatile(addr as *mut u32, value); }
+                self.text.add_text(&mut format!("0x{:08x} <- 0x{:08x}", addr, value));
+            }
+            _ => {
+                self.text.add_text(&mut String::from("poke <addr> <value>"));
+            }
+        }
+    }
+
+    /// `qoi <addr> <len>` -- decode a QOI-encoded image sitting in RAM (no
+    /// flash controller or camera frame source exists in this tree yet, so
+    /// this is the same raw-address bring-up path `peek`/`poke` already use)
+    /// and dither-blit it straight to the panel at (0, 0). Steals its own
+    /// `BetrustedDisplay` the way `hal_lcd::LockedBetrustedDisplay::new()`
+    /// does, rather than threading the render loop's display handle through
+    /// the REPL just for this one command.
+    fn do_qoi_cmd(&mut self) {
+        let rest = self.cmd.trim()[3..].trim();
+        let mut args = rest.split_whitespace();
+        let addr = args.next().and_then(parse_num);
+        let len = args.next().and_then(parse_num);
+
+        let (addr, len) = match (addr, len) {
+            (Some(addr), Some(len)) => (addr, len as usize),
+            _ => {
+                self.text.add_text(&mut String::from("qoi <addr> <len>"));
+                return;
+            }
+        };
+
+        let data = unsafe { core::slice::from_raw_parts(addr as *const u8, len) };
+        let mut display = BetrustedDisplay::new();
+        match draw_qoi(&mut display, Point::new(0, 0), data) {
+            Ok(()) => {
+                display.flush().ok();
+                self.text.add_text(&mut String::from("qoi: blitted"));
+            }
+            Err(e) => self.text.add_text(&mut format!("qoi: decode failed ({:?})", e)),
         }
     }
 
@@ -425,206 +941,121 @@ impl TextArea {
     }
 }
 
-#[entry]
-fn main() -> ! {
-    let p = betrusted_pac::Peripherals::take().unwrap();
-    com_txrx(&p, 0x9003 as u16);  // 0x90cc specifies power set command. bit 0 set means EC stays on; bit 1 means power SoC on
-    unsafe{ p.POWER.power.write(|w| w.self_().bit(true).state().bits(3)); }
-
-    p.SRAM_EXT.read_config.write( |w| w.trigger().bit(true) );  // check SRAM config
-    i2c_init(&p, CONFIG_CLOCK_FREQUENCY / 1_000_000);
-    time_init(&p);
-
-    let cr = p.SRAM_EXT.config_status0.read().bits(); // pull out config params for debug
-    unsafe {
-        let heap_start = &_sheap as *const u8 as usize;
-        let heap_size = &_heap_size as *const u8 as usize;
-        ALLOCATOR.init(heap_start, heap_size);
-        DBGSTR[4] = heap_start as u32;  // some debug visibility on heap initial parameters
-        DBGSTR[6] = heap_size as u32;
-        DBGSTR[2] = cr;
-    }
-
-    let display: LockedBtDisplay = LockedBtDisplay::new();
-    display.lock().init(CONFIG_CLOCK_FREQUENCY);
-
-    let mut keyboard: KeyManager = KeyManager::new();
-
-    // initialize vibe motor patch
-    unsafe{ p.GPIO.drive.write(|w| w.bits(4)); }
-    unsafe{ p.GPIO.output.write(|w| w.bits(0)); }
+/// state touched by more than one of `main`'s async tasks
+struct Shared {
+    repl: Repl,
+    keyboard: KeyManager,
+    tx_index: usize,
+    gg_array: [u16; 4],
+    bouncy_ball: Bounce,
+    nd: u8,
+    d1: char,
+    d2: char,
+    nu: u8,
+    u1: char,
+    u2: char,
+    /// the two TRNG noise channels, generalized from the old fixed-300,
+    /// fixed-`/32` inline graph into the reusable autoscaling widget
+    noise_plot: TimeSeriesPlot,
+    noise0_trace: usize,
+    noise1_trace: usize,
+}
 
-    let radius: u32 = 14;
-    let size: Size = display.lock().size();
-    let mut cur_time: u32 = get_time_ms(&p);
-    let mut _stat_array: [u16; 10] = [0; 10];
-    let mut gg_array: [u16; 4] = [0; 4];
-    let mut line_height: i32 = 18;
+/// redraws the whole frame every round, the same layout the old monolithic
+/// loop did -- but pushes it with [`BetrustedDisplay::flush_dirty`] instead
+/// of a plain `flush()`, so a line whose content came back pixel-identical
+/// (the ball re-stamped over its own old position, unchanged REPL text,
+/// the graph's flat region) costs CPU time re-drawing but no SPI time
+/// re-sending
+async fn display_task(display: &LockedBtDisplay, shared: Rc<RefCell<Shared>>, size: Size) {
     let left_margin: i32 = 10;
-    let mut bouncy_ball: Bounce = Bounce::new(radius, Rectangle::new(Point::new(0, line_height * 21), Point::new(size.width as i32, size.height as i32 - 1)));
-    let mut tx_index: usize = 0;
-    let mut repl: Repl = Repl::new();
-
-    let mut nd: u8 = 0;
-    let mut d1: char = ' ';
-    let mut d2: char = ' ';
-    let mut nu: u8 = 0;
-    let mut u1: char = ' ';
-    let mut u2: char = ' ';
     loop {
+        let p = unsafe { betrusted_pac::Peripherals::steal() };
         display.lock().clear();
-        if repl.power == false {
+
+        if !shared.borrow().repl.power {
             Font12x16::render_str("Betrusted in Standby")
-            .stroke_color(Some(BinaryColor::On))
-            .translate(Point::new(50, 250))
-            .draw(&mut *display.lock());
+                .stroke_color(Some(BinaryColor::On))
+                .translate(Point::new(50, 250))
+                .draw(&mut *display.lock());
 
             Font12x16::render_str("Press '0' to power on")
-            .stroke_color(Some(BinaryColor::On))
-            .translate(Point::new(40, 270))
-            .draw(&mut *display.lock());
+                .stroke_color(Some(BinaryColor::On))
+                .translate(Point::new(40, 270))
+                .draw(&mut *display.lock());
 
             display.lock().blocking_flush();
 
-            unsafe{p.POWER.power.write(|w| w.self_().bit(false).state().bits(1));} // FIXME: figure out how to float the state bit while system is running...
-            com_txrx(&p, 0x9005 as u16);  // 0x90cc specifies power set command. bit 0 set means EC stays on; bit 2 set means fast discharge of FPGA domain
+            unsafe { p.POWER.power.write(|w| w.self_().bit(false).state().bits(1)); } // FIXME: figure out how to float the state bit while system is running...
+            com_txrx(&p, 0x9005 as u16); // 0x90cc specifies power set command. bit 0 set means EC stays on; bit 2 set means fast discharge of FPGA domain
 
-            continue; // this creates the illusion of being powered off even if we're plugged in
+            YieldNow::new().await;
+            continue;
         }
-        let mut cur_line: i32 = 5;
 
-        let uptime = format!{"Uptime {}s", (get_time_ms(&p) / 1000) as u32};
-        line_height = 18;
+        let mut cur_line: i32 = 5;
+        let uptime = format!("Uptime {}s", (get_time_ms(&p) / 1000) as u32);
+        let mut line_height: i32 = 18;
         Font12x16::render_str(&uptime)
-        .stroke_color(Some(BinaryColor::On))
-        .translate(Point::new(left_margin,cur_line))
-        .draw(&mut *display.lock());
-        cur_line += line_height;
-
-        // power state testing ONLY - force a power off in 5 seconds
-        /*
-        if get_time_ms(&p) > 5000 {
-            repl.force_poweroff();
-        }
-        */
-
-        bouncy_ball.update();
-        let circle = egcircle!(bouncy_ball.loc, bouncy_ball.radius, 
-                               stroke_color = Some(BinaryColor::Off), fill_color = Some(BinaryColor::On));
-        circle.draw(&mut *display.lock());
-        
-        // ping the EC and update various records over time
-        if get_time_ms(&p) - cur_time > 250 {
-            cur_time = get_time_ms(&p);
-            if tx_index == 0 {
-                com_txrx(&p, 0x7000 as u16); // send the pointer reset command
-            } else if tx_index < gg_array.len() + 1 {
-                gg_array[tx_index - 1] = com_txrx(&p, 0xDEAD) as u16; // the transmit is a dummy byte
-            }
-            tx_index += 1;
-            tx_index = tx_index % (gg_array.len() + 2);
-        }
-        /*
-        for i in 0..4 {
-            // but update the result every loop iteration
-            let dbg = format!{"s{}: 0x{:04x}  s{}: 0x{:04x}", i*2, stat_array[i*2], i*2+1, stat_array[i*2+1]};
-            Font12x16::render_str(&dbg)
             .stroke_color(Some(BinaryColor::On))
             .translate(Point::new(left_margin, cur_line))
             .draw(&mut *display.lock());
-            cur_line += line_height;
-        }*/
-        let dbg = format!{"voltage: {}mV", gg_array[2]};
-        Font12x16::render_str(&dbg)
-        .stroke_color(Some(BinaryColor::On))
-        .translate(Point::new(left_margin, cur_line))
-        .draw(&mut *display.lock());
-
         cur_line += line_height;
-        let dbg = format!{"avg current: {}mA", (gg_array[0] as i16)};
-        Font12x16::render_str(&dbg)
-        .stroke_color(Some(BinaryColor::On))
-        .translate(Point::new(left_margin, cur_line))
-        .draw(&mut *display.lock());
 
-        cur_line += line_height;
-        let dbg = format!{"sby current: {}mA", (gg_array[1] as i16)};
-        Font12x16::render_str(&dbg)
-        .stroke_color(Some(BinaryColor::On))
-        .translate(Point::new(left_margin, cur_line))
-        .draw(&mut *display.lock());
-
-        let (keydown, keyup) = keyboard.update();
-        if keydown.is_some() { 
-            let mut keyvect = keydown.unwrap();
-            nd = keyvect.len() as u8;
-            
-            if nd >= 1 {
-                let (r, c) = keyvect.pop().unwrap();
-                let scancode = map_dvorak((r,c));
-                let c: char;
-                match scancode.key {
-                    None => c = ' ',
-                    _ => c = scancode.key.unwrap(),
-                }
-                d1 = c;
-                repl.input_char(c);
-            }
-            if nd >= 2 {
-                let (r, c) = keyvect.pop().unwrap();
-                let scancode = map_dvorak((r,c));
-                let c: char;
-                match scancode.key {
-                    None => c = ' ',
-                    _ => c = scancode.key.unwrap(),
-                }
-                d2 = c;
-            }
+        {
+            let mut state = shared.borrow_mut();
+            state.bouncy_ball.update();
+            let circle = egcircle!(state.bouncy_ball.loc, state.bouncy_ball.radius,
+                                   stroke_color = Some(BinaryColor::Off), fill_color = Some(BinaryColor::On));
+            circle.draw(&mut *display.lock());
         }
 
-        if keyup.is_some() { 
-            let mut keyvect = keyup.unwrap();
-            nu = keyvect.len() as u8;
-            
-            if nu >= 1 {
-                let (r, c) = keyvect.pop().unwrap();
-                let scancode = map_dvorak((r,c));
-                let c: char;
-                match scancode.key {
-                    None => c = ' ',
-                    _ => c = scancode.key.unwrap(),
-                }
-                u1 = c;
-            }
-            if nu >= 2 {
-                let (r, c) = keyvect.pop().unwrap();
-                let scancode = map_dvorak((r,c));
-                let c: char;
-                match scancode.key {
-                    None => c = ' ',
-                    _ => c = scancode.key.unwrap(),
-                }
-                u2 = c;
-            }
+        {
+            let state = shared.borrow();
+            let dbg = format!("voltage: {}mV", state.gg_array[2]);
+            Font12x16::render_str(&dbg)
+                .stroke_color(Some(BinaryColor::On))
+                .translate(Point::new(left_margin, cur_line))
+                .draw(&mut *display.lock());
+
+            cur_line += line_height;
+            let dbg = format!("avg current: {}mA", (state.gg_array[0] as i16));
+            Font12x16::render_str(&dbg)
+                .stroke_color(Some(BinaryColor::On))
+                .translate(Point::new(left_margin, cur_line))
+                .draw(&mut *display.lock());
+
+            cur_line += line_height;
+            let dbg = format!("sby current: {}mA", (state.gg_array[1] as i16));
+            Font12x16::render_str(&dbg)
+                .stroke_color(Some(BinaryColor::On))
+                .translate(Point::new(left_margin, cur_line))
+                .draw(&mut *display.lock());
         }
 
         cur_line += line_height;
-        let dbg = format!{"nd:{} d1:{} d2:{}", nd, d1, d2};
-        Font12x16::render_str(&dbg)
-        .stroke_color(Some(BinaryColor::On))
-        .translate(Point::new(left_margin, cur_line))
-        .draw(&mut *display.lock());
+        {
+            let state = shared.borrow();
+            let dbg = format!("nd:{} d1:{} d2:{}", state.nd, state.d1, state.d2);
+            Font12x16::render_str(&dbg)
+                .stroke_color(Some(BinaryColor::On))
+                .translate(Point::new(left_margin, cur_line))
+                .draw(&mut *display.lock());
+        }
 
         cur_line += line_height;
-        let dbg = format!{"nu:{} u1:{} u2:{}", nu, u1, u2};
-        Font12x16::render_str(&dbg)
-        .stroke_color(Some(BinaryColor::On))
-        .translate(Point::new(left_margin, cur_line))
-        .draw(&mut *display.lock());
-        
+        {
+            let state = shared.borrow();
+            let dbg = format!("nu:{} u1:{} u2:{}", state.nu, state.u1, state.u2);
+            Font12x16::render_str(&dbg)
+                .stroke_color(Some(BinaryColor::On))
+                .translate(Point::new(left_margin, cur_line))
+                .draw(&mut *display.lock());
+        }
+
         // draw a demarcation line
         cur_line += line_height + 2;
-        Line::<BinaryColor>::new(Point::new(left_margin, cur_line), 
+        Line::<BinaryColor>::new(Point::new(left_margin, cur_line),
         Point::new(size.width as i32 - left_margin, cur_line))
         .stroke_color(Some(BinaryColor::On))
         .draw(&mut *display.lock());
@@ -633,63 +1064,205 @@ fn main() -> ! {
         cur_line += 4;
         line_height = 15; // shorter line, smaller font
 
-        for line in (0..NUM_LINES).rev() {
-            let out = repl.get_line(line);
-            Font8x16::render_str(&out)
+        const FONT8X16_CHAR_WIDTH: i32 = 8;
+        let column_width = ((size.width as i32 - left_margin) / FONT8X16_CHAR_WIDTH).max(1) as usize;
+
+        {
+            let state = shared.borrow();
+            for line in (0..NUM_LINES).rev() {
+                let out = state.repl.get_line(line);
+                for row in wrap(&out, column_width) {
+                    Font8x16::render_str(&row.text)
+                    .stroke_color(Some(BinaryColor::On))
+                    .translate(Point::new(left_margin, cur_line))
+                    .draw(&mut *display.lock());
+                    cur_line += line_height;
+                }
+            }
+
+            let cmd = truncate_with_ellipsis(&state.repl.get_cmd(), column_width);
+            Font8x16::render_str(&cmd)
             .stroke_color(Some(BinaryColor::On))
             .translate(Point::new(left_margin, cur_line))
             .draw(&mut *display.lock());
+
             cur_line += line_height;
-        }
+            let input = truncate_with_ellipsis(&state.repl.get_input(), column_width);
+            Font8x16::render_str(&input)
+            .stroke_color(Some(BinaryColor::On))
+            .translate(Point::new(left_margin, cur_line))
+            .draw(&mut *display.lock());
 
-        let cmd = repl.get_cmd();
-        Font8x16::render_str(&cmd)
-        .stroke_color(Some(BinaryColor::On))
-        .translate(Point::new(left_margin, cur_line))
-        .draw(&mut *display.lock());
+            let caret_x = left_margin + string_width(&input) as i32 * FONT8X16_CHAR_WIDTH;
+            draw_cursor(
+                &mut *display.lock(),
+                Point::new(caret_x, cur_line),
+                Size::new(FONT8X16_CHAR_WIDTH as u32, line_height as u32),
+                &state.repl.get_cursor(),
+                get_time_ms(&p),
+            );
+        }
 
         cur_line += line_height;
-        let mut input = repl.get_input();
-        if (get_time_ms(&p) / 500) % 2 == 0 {
-            input.push('_'); // add an insertion carat
+        // aligned to a whole number of character cells, same grid the
+        // scrollback/input text and cursor above are laid out on
+        let graph_margin = FONT8X16_CHAR_WIDTH * 2;
+        let graph_bounds = Rectangle::new(
+            Point::new(graph_margin, cur_line),
+            Point::new(size.width as i32 - graph_margin, cur_line + 128),
+        );
+        shared.borrow().noise_plot.draw(&mut *display.lock(), graph_bounds, false);
+
+        display.lock().flush_dirty();
+        YieldNow::new().await;
+    }
+}
+
+/// awaits a 250 ms [`Delay`] instead of hand-tracking a `get_time_ms` delta,
+/// then runs one step of the EC gas-gauge poll's `tx_index` state machine
+async fn ec_poll_task(shared: Rc<RefCell<Shared>>) {
+    loop {
+        Delay::new_ms(250).await;
+
+        let p = unsafe { betrusted_pac::Peripherals::steal() };
+        let mut state = shared.borrow_mut();
+        if state.tx_index == 0 {
+            com_txrx(&p, 0x7000 as u16); // send the pointer reset command
+        } else if state.tx_index < state.gg_array.len() + 1 {
+            let idx = state.tx_index - 1;
+            state.gg_array[idx] = com_txrx(&p, 0xDEAD) as u16; // the transmit is a dummy byte
         }
-        Font8x16::render_str(&input)
-        .stroke_color(Some(BinaryColor::On))
-        .translate(Point::new(left_margin, cur_line))
-        .draw(&mut *display.lock());
+        state.tx_index += 1;
+        state.tx_index = state.tx_index % (state.gg_array.len() + 2);
+    }
+}
 
-        cur_line += line_height;
-        const GRAPH_MARGIN: i32 = 18;
-        Line::<BinaryColor>::new(Point::new(GRAPH_MARGIN, cur_line + 128),
-        Point::new(size.width as i32 - GRAPH_MARGIN, cur_line + 128))
-        .stroke_color(Some(BinaryColor::On))
-        .draw(&mut *display.lock());
-        Line::<BinaryColor>::new(Point::new(GRAPH_MARGIN, cur_line),
-        Point::new(GRAPH_MARGIN, cur_line + 128))
-        .stroke_color(Some(BinaryColor::On))
-        .draw(&mut *display.lock());
-        if repl.get_update_noise() {
-            repl.sample_noise();
-            let noise0: [u16; 300] = repl.get_noise0();
-            let noise1: [u16; 300] = repl.get_noise1();
-            let mut x = GRAPH_MARGIN;
-            for index in 0..299 {
-                Line::<BinaryColor>::new(Point::new(x, cur_line + 64 - noise0[index] as i32 / 32),
-                Point::new(x+1, cur_line + 64 - noise0[index+1] as i32 / 32))
-                .stroke_color(Some(BinaryColor::On))
-                .draw(&mut *display.lock());
-                x = x + 1;
+/// scans the keyboard every round and feeds key-down events into the REPL
+async fn keyboard_task(shared: Rc<RefCell<Shared>>) {
+    loop {
+        let mut state = shared.borrow_mut();
+
+        if let Some(layout) = state.repl.take_pending_layout() {
+            match layout {
+                LayoutName::Qwerty => state.keyboard.set_layout(Box::new(QwertyKeyMap)),
+                LayoutName::Dvorak => state.keyboard.set_layout(Box::new(DvorakKeyMap)),
             }
-            x = GRAPH_MARGIN;
-            for index in 0..299 {
-                Line::<BinaryColor>::new(Point::new(x, cur_line + 128 - noise1[index] as i32 / 32),
-                Point::new(x+1, cur_line + 128 - noise1[index+1] as i32 / 32))
-                .stroke_color(Some(BinaryColor::On))
-                .draw(&mut *display.lock());
-                x = x + 1;
+        }
+        if let Some((trigger, expansion)) = state.repl.take_pending_macro() {
+            state.keyboard.register_macro(trigger, expansion);
+        }
+
+        let events = state.keyboard.update();
+
+        let downs: Vec<&KeyEvent> = events.iter().filter(|e| e.state != KeyState::Released).collect();
+        let ups: Vec<&KeyEvent> = events.iter().filter(|e| e.state == KeyState::Released).collect();
+
+        state.nd = downs.len() as u8;
+        for (i, event) in downs.iter().enumerate() {
+            if let Some(ch) = event.ch {
+                state.repl.input_char(ch);
+                match i {
+                    0 => state.d1 = ch,
+                    1 => state.d2 = ch,
+                    _ => {}
+                }
+            }
+        }
+
+        state.nu = ups.len() as u8;
+        if let Some(event) = ups.get(0) {
+            state.u1 = event.ch.unwrap_or(' ');
+        }
+        if let Some(event) = ups.get(1) {
+            state.u2 = event.ch.unwrap_or(' ');
+        }
+
+        drop(state);
+        YieldNow::new().await;
+    }
+}
+
+/// samples the noise ADC channels whenever the REPL's `non`/`noff` commands
+/// have noise sampling turned on, gated on `update_noise` the same way the
+/// old loop gated its noise graph update
+async fn noise_task(shared: Rc<RefCell<Shared>>) {
+    loop {
+        if shared.borrow().repl.get_update_noise() {
+            let mut state = shared.borrow_mut();
+            state.repl.sample_noise();
+            let (noise0, noise1) = (state.repl.get_noise0(), state.repl.get_noise1());
+            let (trace0, trace1) = (state.noise0_trace, state.noise1_trace);
+            for sample in noise0.iter() {
+                state.noise_plot.push(trace0, *sample as i32);
+            }
+            for sample in noise1.iter() {
+                state.noise_plot.push(trace1, *sample as i32);
             }
         }
+        YieldNow::new().await;
+    }
+}
 
-        display.lock().flush().unwrap();
+#[entry]
+fn main() -> ! {
+    let p = betrusted_pac::Peripherals::take().unwrap();
+    com_txrx(&p, 0x9003 as u16);  // 0x90cc specifies power set command. bit 0 set means EC stays on; bit 1 means power SoC on
+    unsafe{ p.POWER.power.write(|w| w.self_().bit(true).state().bits(3)); }
+
+    p.SRAM_EXT.read_config.write( |w| w.trigger().bit(true) );  // check SRAM config
+    i2c_init(&p, CONFIG_CLOCK_FREQUENCY / 1_000_000);
+    time_init(&p);
+
+    let cr = p.SRAM_EXT.config_status0.read().bits(); // pull out config params for debug
+    unsafe {
+        let heap_start = &_sheap as *const u8 as usize;
+        let heap_size = &_heap_size as *const u8 as usize;
+        ALLOCATOR.init(heap_start, heap_size);
+        DBGSTR[4] = heap_start as u32;  // some debug visibility on heap initial parameters
+        DBGSTR[6] = heap_size as u32;
+        DBGSTR[2] = cr;
+    }
+
+    let display: LockedBtDisplay = LockedBtDisplay::new();
+    display.lock().init(CONFIG_CLOCK_FREQUENCY);
+
+    // initialize vibe motor patch
+    unsafe{ p.GPIO.drive.write(|w| w.bits(4)); }
+    unsafe{ p.GPIO.output.write(|w| w.bits(0)); }
+
+    let radius: u32 = 14;
+    let size: Size = display.lock().size();
+    let mut _stat_array: [u16; 10] = [0; 10];
+    let line_height: i32 = 18;
+
+    let mut noise_plot = TimeSeriesPlot::new();
+    let noise0_trace = noise_plot.add_trace("trng0", 300);
+    let noise1_trace = noise_plot.add_trace("trng1", 300);
+
+    let shared = Rc::new(RefCell::new(Shared {
+        repl: Repl::new(),
+        keyboard: KeyManager::new(),
+        tx_index: 0,
+        gg_array: [0u16; 4],
+        bouncy_ball: Bounce::new(radius, Rectangle::new(Point::new(0, line_height * 21), Point::new(size.width as i32, size.height as i32 - 1))),
+        nd: 0,
+        d1: ' ',
+        d2: ' ',
+        nu: 0,
+        u1: ' ',
+        u2: ' ',
+        noise_plot,
+        noise0_trace,
+        noise1_trace,
+    }));
+
+    let mut executor: Executor = Executor::new();
+    executor.spawn(display_task(&display, shared.clone(), size));
+    executor.spawn(ec_poll_task(shared.clone()));
+    executor.spawn(keyboard_task(shared.clone()));
+    executor.spawn(noise_task(shared.clone()));
+
+    loop {
+        executor.run_once();
     }
 }